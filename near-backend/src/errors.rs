@@ -0,0 +1,257 @@
+use actix_web::http::StatusCode;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fmt;
+
+use crate::models::ApiError;
+
+/// Leads every `InvalidIdentifier` message. Serde keeps only the text of an
+/// id's validation error, so request errors are recognised by it.
+pub const INVALID_IDENTIFIER: &str = "Invalid identifier";
+
+/// Errors that map to a specific API error code and HTTP status instead of
+/// the generic per-endpoint failure.
+#[derive(Debug)]
+pub enum ServiceError {
+    /// A user or project id is empty, too long or could leave the projects directory
+    InvalidIdentifier {
+        kind: &'static str,
+        value: String,
+        reason: &'static str,
+    },
+    /// A submitted file path is absolute, escapes the project root or targets a reserved directory
+    InvalidFilePath { path: String, reason: String },
+    /// A request writes or deletes more files than one change set may hold
+    TooManyFiles { count: usize, max: usize },
+    /// No compile job with the given id is known to this server
+    JobNotFound { job_id: String },
+    /// The compile job already reached a final state and cannot be cancelled
+    JobAlreadyFinished { job_id: String, status: String },
+    /// Every build slot is busy and the wait queue is at capacity
+    QueueFull { queued: usize, retry_after_secs: u64 },
+    /// A newer compile request for the same project arrived before this one started
+    BuildSuperseded { project: String },
+    /// The sandboxed build hit a CPU, memory, file size or disk quota
+    BuildLimitExceeded { limit: String, details: String },
+    /// The project has no build output of the requested kind
+    ArtifactNotFound { project: String, artifact: String },
+    /// The project's build output does not match its crate or its current sources
+    ArtifactMismatch { artifact: String, reason: String },
+    /// The requested toolchain or near-sdk version is not installed on this server
+    UnsupportedVersion {
+        component: String,
+        requested: String,
+        available: Vec<String>,
+    },
+    /// No project template with the given id is registered
+    TemplateNotFound { template: String },
+    /// The user has no project with the given id
+    ProjectNotFound { project: String },
+    /// A project with the target id already exists
+    ProjectExists { project: String },
+    /// The bearer token is missing, invalid or expired, or authentication is not configured
+    Unauthenticated { reason: String },
+    /// The request names a user other than the one its token was issued to
+    UserMismatch { user_id: String },
+    /// The user or client address used up an allowance that resets at `reset_at`
+    RateLimited { limit: String, reset_at: DateTime<Utc> },
+    /// The admin token is missing, wrong, or no token is configured
+    AdminAccessDenied,
+    /// The build ran past its deadline and its process tree was killed
+    CompilationTimeout {
+        elapsed_secs: u64,
+        stdout: String,
+        stderr: String,
+    },
+}
+
+impl ServiceError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServiceError::InvalidIdentifier { .. } => "INVALID_IDENTIFIER",
+            ServiceError::InvalidFilePath { .. } => "INVALID_FILE_PATH",
+            ServiceError::TooManyFiles { .. } => "TOO_MANY_FILES",
+            ServiceError::JobNotFound { .. } => "JOB_NOT_FOUND",
+            ServiceError::JobAlreadyFinished { .. } => "JOB_ALREADY_FINISHED",
+            ServiceError::QueueFull { .. } => "BUILD_QUEUE_FULL",
+            ServiceError::BuildSuperseded { .. } => "BUILD_SUPERSEDED",
+            ServiceError::BuildLimitExceeded { .. } => "BUILD_LIMIT_EXCEEDED",
+            ServiceError::CompilationTimeout { .. } => "COMPILATION_TIMEOUT",
+            ServiceError::ArtifactNotFound { .. } => "ARTIFACT_NOT_FOUND",
+            ServiceError::ArtifactMismatch { .. } => "ARTIFACT_MISMATCH",
+            ServiceError::UnsupportedVersion { .. } => "UNSUPPORTED_VERSION",
+            ServiceError::TemplateNotFound { .. } => "TEMPLATE_NOT_FOUND",
+            ServiceError::ProjectNotFound { .. } => "PROJECT_NOT_FOUND",
+            ServiceError::ProjectExists { .. } => "PROJECT_EXISTS",
+            ServiceError::Unauthenticated { .. } => "UNAUTHENTICATED",
+            ServiceError::UserMismatch { .. } => "USER_MISMATCH",
+            ServiceError::RateLimited { .. } => "RATE_LIMITED",
+            ServiceError::AdminAccessDenied => "ADMIN_ACCESS_DENIED",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ServiceError::InvalidIdentifier { .. } => StatusCode::BAD_REQUEST,
+            ServiceError::InvalidFilePath { .. } => StatusCode::BAD_REQUEST,
+            ServiceError::TooManyFiles { .. } => StatusCode::BAD_REQUEST,
+            ServiceError::JobNotFound { .. } => StatusCode::NOT_FOUND,
+            ServiceError::JobAlreadyFinished { .. } => StatusCode::CONFLICT,
+            ServiceError::QueueFull { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ServiceError::BuildSuperseded { .. } => StatusCode::CONFLICT,
+            ServiceError::BuildLimitExceeded { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ServiceError::CompilationTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            ServiceError::ArtifactNotFound { .. } => StatusCode::NOT_FOUND,
+            ServiceError::ArtifactMismatch { .. } => StatusCode::CONFLICT,
+            ServiceError::UnsupportedVersion { .. } => StatusCode::BAD_REQUEST,
+            ServiceError::TemplateNotFound { .. } => StatusCode::NOT_FOUND,
+            ServiceError::ProjectNotFound { .. } => StatusCode::NOT_FOUND,
+            ServiceError::ProjectExists { .. } => StatusCode::CONFLICT,
+            ServiceError::Unauthenticated { .. } => StatusCode::UNAUTHORIZED,
+            ServiceError::UserMismatch { .. } => StatusCode::FORBIDDEN,
+            ServiceError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ServiceError::AdminAccessDenied => StatusCode::FORBIDDEN,
+        }
+    }
+
+    /// Extra context for `ApiError.details`, such as output captured before a timeout
+    pub fn details(&self) -> Option<String> {
+        match self {
+            ServiceError::BuildLimitExceeded { details, .. } => Some(details.clone()),
+            ServiceError::CompilationTimeout { stdout, stderr, .. } => {
+                Some(format!("{}{}", stdout, stderr))
+            }
+            ServiceError::RateLimited { reset_at, .. } => {
+                Some(reset_at.to_rfc3339_opts(SecondsFormat::Secs, true))
+            }
+            _ => None,
+        }
+    }
+
+    /// Seconds a client should wait before retrying, sent as `Retry-After`
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            ServiceError::QueueFull {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+            ServiceError::RateLimited { reset_at, .. } => {
+                Some((*reset_at - Utc::now()).num_seconds().max(1) as u64)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::InvalidIdentifier { kind, value, reason } => {
+                write!(f, "{}: {} '{}' {}", INVALID_IDENTIFIER, kind, value, reason)
+            }
+            ServiceError::InvalidFilePath { path, reason } => {
+                write!(f, "Invalid file path '{}': {}", path, reason)
+            }
+            ServiceError::TooManyFiles { count, max } => {
+                write!(f, "Too many files in request ({}, maximum is {})", count, max)
+            }
+            ServiceError::JobNotFound { job_id } => write!(f, "Compile job {} not found", job_id),
+            ServiceError::JobAlreadyFinished { job_id, status } => {
+                write!(f, "Compile job {} already finished with status '{}'", job_id, status)
+            }
+            ServiceError::QueueFull {
+                queued,
+                retry_after_secs,
+            } => write!(
+                f,
+                "Build queue is full ({} builds waiting), retry in {} seconds",
+                queued, retry_after_secs
+            ),
+            ServiceError::BuildSuperseded { project } => write!(
+                f,
+                "Build of {} was superseded by a newer compile request",
+                project
+            ),
+            ServiceError::BuildLimitExceeded { limit, details } => {
+                write!(f, "Build exceeded its {} limit: {}", limit, details)
+            }
+            ServiceError::ArtifactNotFound { project, artifact } => write!(
+                f,
+                "No {} artifact found for {}; compile the project first",
+                artifact, project
+            ),
+            ServiceError::ArtifactMismatch { artifact, reason } => {
+                write!(f, "Cannot use the {} artifact: {}", artifact, reason)
+            }
+            ServiceError::UnsupportedVersion {
+                component,
+                requested,
+                available,
+            } => write!(
+                f,
+                "{} '{}' is not available; choose one of: {}",
+                component,
+                requested,
+                if available.is_empty() {
+                    "none (the server default is used)".to_string()
+                } else {
+                    available.join(", ")
+                }
+            ),
+            ServiceError::TemplateNotFound { template } => {
+                write!(f, "Project template '{}' not found", template)
+            }
+            ServiceError::ProjectNotFound { project } => write!(f, "Project {} not found", project),
+            ServiceError::ProjectExists { project } => write!(f, "Project {} already exists", project),
+            ServiceError::Unauthenticated { reason } => write!(f, "Authentication required: {}", reason),
+            ServiceError::UserMismatch { user_id } => {
+                write!(f, "The access token does not belong to user '{}'", user_id)
+            }
+            ServiceError::RateLimited { limit, reset_at } => write!(
+                f,
+                "Rate limit of {} reached; resets at {}",
+                limit,
+                reset_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ),
+            ServiceError::AdminAccessDenied => write!(f, "A valid X-Admin-Token header is required"),
+            ServiceError::CompilationTimeout { elapsed_secs, .. } => write!(
+                f,
+                "Compilation timed out after {} seconds and was stopped",
+                elapsed_secs
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+/// Classify a failure into the HTTP status and `ApiError` reported to clients,
+/// falling back to the endpoint's generic code when it is not a `ServiceError`.
+pub fn to_api_error(error: &anyhow::Error, code: &str, message: &str) -> (StatusCode, ApiError) {
+    match error.downcast_ref::<ServiceError>() {
+        Some(service_error) => (
+            service_error.status(),
+            ApiError {
+                code: service_error.code().to_string(),
+                message: service_error.to_string(),
+                details: service_error.details(),
+            },
+        ),
+        None => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError {
+                code: code.to_string(),
+                message: message.to_string(),
+                details: Some(error.to_string()),
+            },
+        ),
+    }
+}
+
+/// The `InvalidIdentifier` message inside a request deserialization error,
+/// without serde_json's " at line N column M" position
+pub fn identifier_error(message: &str) -> Option<&str> {
+    let message = &message[message.find(INVALID_IDENTIFIER)?..];
+    Some(match message.rfind(" at line ") {
+        Some(end) if message[end..].contains(" column ") => &message[..end],
+        _ => message,
+    })
+}
@@ -1,11 +1,35 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::{InternalError, JsonPayloadError, PathError};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Result};
+use futures_util::stream::{self, StreamExt};
 use log::{error, info};
+use sha2::{Digest, Sha256};
+use std::env;
+use subtle::ConstantTimeEq;
+use tokio::sync::broadcast::error::RecvError;
 
+use crate::errors::{identifier_error, to_api_error, ServiceError};
 use crate::models::{
-    ApiResponse, CompileRequest, CompileResponse, DeployRequest, DeployResponse, HealthResponse,
-    MethodCallRequest, MethodCallResponse,
+    ApiResponse, CompileEvent, CompileRequest, CompileResponse, DeployRequest, DeployResponse, HealthResponse,
+    JobStatusResponse, MethodCallRequest, MethodCallResponse, ProjectId, ProjectIdRequest, ToolchainsResponse, UserId,
 };
-use crate::services::{compilation::compile_contract, deployment::deploy_contract, method_call::call_contract_method};
+use crate::services::auth::{AuthenticatedUser, Authenticator};
+use crate::services::artifacts::{project_dir, resolve_artifact, ArtifactKind, Freshness};
+use crate::services::build_queue::BuildQueue;
+use crate::services::jobs::JobRegistry;
+use crate::services::project_locks::{ProjectGuard, ProjectLocks};
+use crate::services::rate_limits::{LimitedAction, RateLimiter};
+use crate::services::projects::{
+    delete_project, fork_project, list_projects, project_details, rename_project,
+};
+use crate::services::reaper::Reaper;
+use crate::services::templates::TemplateRegistry;
+use crate::services::toolchains::ToolchainCatalog;
+use crate::services::{compilation::compile_contract, deployment::{deploy_contract, projected_funding}, method_call::call_contract_method};
+use crate::utils::code_hash;
 
 pub async fn health_handler() -> Result<HttpResponse> {
     let response = HealthResponse {
@@ -20,13 +44,119 @@ pub async fn health_handler() -> Result<HttpResponse> {
     )))
 }
 
-pub async fn compile_handler(req: web::Json<CompileRequest>) -> Result<HttpResponse> {
+pub async fn toolchains_handler() -> Result<HttpResponse> {
+    let catalog = ToolchainCatalog::from_env();
+    let defaults = catalog.default_selection();
+    let response = ToolchainsResponse {
+        toolchains: catalog.toolchains,
+        near_sdk_versions: catalog.near_sdk_versions,
+        default_toolchain: defaults.toolchain,
+        default_near_sdk_version: defaults.near_sdk,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        response,
+        "Available toolchains and near-sdk versions".to_string(),
+    )))
+}
+
+pub async fn templates_handler() -> Result<HttpResponse> {
+    match TemplateRegistry::from_env().list() {
+        Ok(templates) => Ok(HttpResponse::Ok().json(ApiResponse::success(
+            templates,
+            "Available project templates".to_string(),
+        ))),
+        Err(e) => {
+            error!("Failed to list templates: {:#}", e);
+            Ok(error_response::<()>(&e, "TEMPLATES_UNAVAILABLE", "Failed to list project templates"))
+        }
+    }
+}
+
+pub async fn template_handler(path: web::Path<String>) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    match TemplateRegistry::from_env().get(&id) {
+        Ok(template) => Ok(HttpResponse::Ok().json(ApiResponse::success(
+            template,
+            "Project template".to_string(),
+        ))),
+        Err(e) => {
+            error!("Failed to load template {}: {:#}", id, e);
+            Ok(error_response::<()>(&e, "TEMPLATES_UNAVAILABLE", "Failed to load project template"))
+        }
+    }
+}
+
+pub async fn compile_handler(
+    http: HttpRequest,
+    user: AuthenticatedUser,
+    req: web::Json<CompileRequest>,
+    jobs: web::Data<JobRegistry>,
+    queue: web::Data<BuildQueue>,
+    locks: web::Data<ProjectLocks>,
+    limiter: web::Data<RateLimiter>,
+) -> Result<HttpResponse> {
     info!(
         "Compilation request received for project: {}",
         req.project_id
     );
 
-    match compile_contract(&req.code, &req.user_id, &req.project_id).await {
+    let user_id = match user.resolve(req.user_id.as_ref()) {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            info!("Rejecting compilation for project {}: {}", req.project_id, e);
+            return Ok(error_response::<CompileResponse>(
+                &e.into(),
+                "COMPILATION_FAILED",
+                "Failed to compile contract",
+            ))
+        }
+    };
+
+    // Queue first so a build turned away for a full queue keeps its quota;
+    // a ticket refused by the limiter is dropped and frees its place
+    let client_ip = limiter.client_ip(&http);
+    let admitted = queue.enqueue().map_err(anyhow::Error::from).and_then(|ticket| {
+        limiter.acquire(LimitedAction::Compile, Some(&user_id), client_ip.as_deref())?;
+        Ok(ticket)
+    });
+    let ticket = match admitted {
+        Ok(ticket) => ticket,
+        Err(e) => {
+            info!("Rejecting compilation for project {}: {}", req.project_id, e);
+            return Ok(error_response::<CompileResponse>(
+                &e,
+                "COMPILATION_FAILED",
+                "Failed to compile contract",
+            ));
+        }
+    };
+
+    // Supersedes older requests for the project that are still waiting
+    let claim = locks.claim_compile(&user_id, &req.project_id);
+
+    if req.run_async {
+        let request = req.into_inner();
+        let (owner, project_id) = (user_id.to_string(), request.project_id.clone());
+        let job = jobs.spawn(&owner, &project_id, ticket, claim, |events| async move {
+            compile_contract(&user_id, &request, &|event| events.emit(event)).await
+        });
+        return Ok(HttpResponse::Accepted().json(ApiResponse::success(
+            job,
+            "Compilation job queued".to_string(),
+        )));
+    }
+
+    // The project lock comes first so waiting on it never holds a build slot
+    let result = match claim.lock().await {
+        Ok(_guard) => {
+            let _permit = ticket.acquire().await;
+            compile_contract(&user_id, &req, &|_| {}).await
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    match result {
         Ok(compile_result) => {
             info!("Compilation completed for project: {}", req.project_id);
             Ok(HttpResponse::Ok().json(ApiResponse::success(
@@ -36,21 +166,381 @@ pub async fn compile_handler(req: web::Json<CompileRequest>) -> Result<HttpRespo
         }
         Err(e) => {
             error!("Compilation failed for project {}: {}", req.project_id, e);
-            Ok(HttpResponse::InternalServerError().json(
-                ApiResponse::<CompileResponse>::error(
-                    "COMPILATION_FAILED".to_string(),
-                    "Failed to compile contract".to_string(),
-                    Some(e.to_string()),
-                ),
+            Ok(error_response::<CompileResponse>(
+                &e,
+                "COMPILATION_FAILED",
+                "Failed to compile contract",
             ))
         }
     }
 }
 
-pub async fn deploy_handler(req: web::Json<DeployRequest>) -> Result<HttpResponse> {
+pub async fn job_status_handler(
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+    jobs: web::Data<JobRegistry>,
+) -> Result<HttpResponse> {
+    match owned_job(&jobs, &user, &path) {
+        Ok(job) => Ok(HttpResponse::Ok().json(ApiResponse::success(
+            job,
+            "Compile job status retrieved".to_string(),
+        ))),
+        Err(e) => Ok(error_response::<JobStatusResponse>(
+            &e.into(),
+            "JOB_STATUS_FAILED",
+            "Failed to retrieve compile job",
+        )),
+    }
+}
+
+/// Stream a compile job's output as Server-Sent Events, replaying what was
+/// produced before the client connected and ending with the final result
+pub async fn job_events_handler(
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+    jobs: web::Data<JobRegistry>,
+) -> Result<HttpResponse> {
+    let subscription = match owned_job(&jobs, &user, &path).and_then(|_| jobs.subscribe(&path)) {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            return Ok(error_response::<JobStatusResponse>(
+                &e.into(),
+                "JOB_EVENTS_FAILED",
+                "Failed to stream compile job events",
+            ))
+        }
+    };
+
+    let live = stream::unfold(subscription.receiver, |receiver| async move {
+        let mut receiver = receiver?;
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let next = if event.is_terminal() { None } else { Some(receiver) };
+                    return Some((event, next));
+                }
+                // Slow clients miss some output lines rather than stalling the build
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    let body = stream::iter(subscription.history)
+        .chain(live)
+        .map(|event| Ok::<_, actix_web::Error>(web::Bytes::from(sse_frame(&event))));
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(body))
+}
+
+pub async fn cancel_job_handler(
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+    jobs: web::Data<JobRegistry>,
+) -> Result<HttpResponse> {
+    info!("Cancellation requested for compile job: {}", path);
+
+    match owned_job(&jobs, &user, &path).and_then(|_| jobs.cancel(&path)) {
+        Ok(job) => Ok(HttpResponse::Ok().json(ApiResponse::success(
+            job,
+            "Compile job cancelled".to_string(),
+        ))),
+        Err(e) => Ok(error_response::<JobStatusResponse>(
+            &e.into(),
+            "JOB_CANCEL_FAILED",
+            "Failed to cancel compile job",
+        )),
+    }
+}
+
+/// Look up a job, treating other users' jobs as unknown
+fn owned_job(
+    jobs: &JobRegistry,
+    user: &AuthenticatedUser,
+    job_id: &str,
+) -> Result<JobStatusResponse, ServiceError> {
+    let job = jobs.get(job_id)?;
+    if !user.owns(&job.user_id) {
+        return Err(ServiceError::JobNotFound {
+            job_id: job_id.to_string(),
+        });
+    }
+    Ok(job)
+}
+
+/// Download the WASM or ABI of a project's last build, exactly as a deploy would use it
+pub async fn artifact_handler(
+    user: AuthenticatedUser,
+    path: web::Path<(UserId, ProjectId, String)>,
+    locks: web::Data<ProjectLocks>,
+) -> Result<HttpResponse> {
+    let (user_id, project_id, kind) = path.into_inner();
+
+    let result = async {
+        user.resolve(Some(&user_id))?;
+        let project_path = project_dir(&user_id, &project_id);
+        let not_found = || ServiceError::ArtifactNotFound {
+            project: format!("{}/{}", user_id, project_id),
+            artifact: kind.clone(),
+        };
+        let artifact = ArtifactKind::parse(&kind).ok_or_else(not_found)?;
+
+        // Never serve a file a running build is still writing
+        let _guard = locks.claim_deploy(&user_id, &project_id).lock().await?;
+        let file = resolve_artifact(&project_path, artifact, Freshness::NewerThanSources)?;
+        let contents = tokio::fs::read(&file).await?;
+        Ok::<_, anyhow::Error>((artifact, file, contents))
+    }
+    .await;
+
+    match result {
+        Ok((artifact, file, contents)) => {
+            let file_name = file
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let mut response = HttpResponse::Ok();
+            response.content_type(artifact.content_type()).insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", file_name),
+            ));
+            if artifact == ArtifactKind::Wasm {
+                response.insert_header(("X-Code-Hash", code_hash(&contents)));
+            }
+            Ok(response.body(contents))
+        }
+        Err(e) => {
+            error!("Artifact download failed for {}/{}: {}", user_id, project_id, e);
+            Ok(error_response::<()>(
+                &e,
+                "ARTIFACT_DOWNLOAD_FAILED",
+                "Failed to read build artifact",
+            ))
+        }
+    }
+}
+
+pub async fn list_projects_handler(
+    user: AuthenticatedUser,
+    path: web::Path<UserId>,
+) -> Result<HttpResponse> {
+    let user_id = path.into_inner();
+    let result = user
+        .resolve(Some(&user_id))
+        .map_err(anyhow::Error::from)
+        .and_then(|user_id| list_projects(&user_id));
+
+    match result {
+        Ok(projects) => Ok(HttpResponse::Ok().json(ApiResponse::success(
+            projects,
+            "Projects listed".to_string(),
+        ))),
+        Err(e) => {
+            error!("Failed to list projects for {}: {:#}", user_id, e);
+            Ok(error_response::<()>(&e, "PROJECT_OPERATION_FAILED", "Failed to list projects"))
+        }
+    }
+}
+
+pub async fn project_handler(
+    user: AuthenticatedUser,
+    path: web::Path<(UserId, ProjectId)>,
+    locks: web::Data<ProjectLocks>,
+) -> Result<HttpResponse> {
+    let (user_id, project_id) = path.into_inner();
+
+    let result = async {
+        user.resolve(Some(&user_id))?;
+        // Read a consistent tree rather than one a build is writing
+        let _guard = locks.claim_deploy(&user_id, &project_id).lock().await?;
+        project_details(&user_id, &project_id)
+    }
+    .await;
+
+    match result {
+        Ok(project) => Ok(HttpResponse::Ok().json(ApiResponse::success(
+            project,
+            "Project loaded".to_string(),
+        ))),
+        Err(e) => {
+            error!("Failed to load project {}/{}: {:#}", user_id, project_id, e);
+            Ok(error_response::<()>(&e, "PROJECT_OPERATION_FAILED", "Failed to load project"))
+        }
+    }
+}
+
+pub async fn fork_project_handler(
+    user: AuthenticatedUser,
+    path: web::Path<(UserId, ProjectId)>,
+    req: web::Json<ProjectIdRequest>,
+    locks: web::Data<ProjectLocks>,
+) -> Result<HttpResponse> {
+    let (user_id, project_id) = path.into_inner();
+
+    let result = async {
+        user.resolve(Some(&user_id))?;
+        let _guards = lock_pair(&locks, &user_id, &project_id, &req.new_project_id).await?;
+        fork_project(&user_id, &project_id, &req.new_project_id)
+    }
+    .await;
+
+    match result {
+        Ok(project) => {
+            info!("Forked {}/{} into {}", user_id, project_id, req.new_project_id);
+            Ok(HttpResponse::Created().json(ApiResponse::success(
+                project,
+                "Project forked".to_string(),
+            )))
+        }
+        Err(e) => {
+            error!("Failed to fork project {}/{}: {:#}", user_id, project_id, e);
+            Ok(error_response::<()>(&e, "PROJECT_OPERATION_FAILED", "Failed to fork project"))
+        }
+    }
+}
+
+pub async fn rename_project_handler(
+    user: AuthenticatedUser,
+    path: web::Path<(UserId, ProjectId)>,
+    req: web::Json<ProjectIdRequest>,
+    locks: web::Data<ProjectLocks>,
+) -> Result<HttpResponse> {
+    let (user_id, project_id) = path.into_inner();
+
+    let result = async {
+        user.resolve(Some(&user_id))?;
+        let _guards = lock_pair(&locks, &user_id, &project_id, &req.new_project_id).await?;
+        rename_project(&user_id, &project_id, &req.new_project_id)
+    }
+    .await;
+
+    match result {
+        Ok(project) => {
+            locks.forget(&user_id, &project_id);
+            info!("Renamed {}/{} to {}", user_id, project_id, req.new_project_id);
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                project,
+                "Project renamed".to_string(),
+            )))
+        }
+        Err(e) => {
+            error!("Failed to rename project {}/{}: {:#}", user_id, project_id, e);
+            Ok(error_response::<()>(&e, "PROJECT_OPERATION_FAILED", "Failed to rename project"))
+        }
+    }
+}
+
+pub async fn delete_project_handler(
+    user: AuthenticatedUser,
+    path: web::Path<(UserId, ProjectId)>,
+    locks: web::Data<ProjectLocks>,
+) -> Result<HttpResponse> {
+    let (user_id, project_id) = path.into_inner();
+
+    let result = async {
+        user.resolve(Some(&user_id))?;
+        let _guard = locks.claim_deploy(&user_id, &project_id).lock().await?;
+        delete_project(&user_id, &project_id)
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            locks.forget(&user_id, &project_id);
+            info!("Deleted project {}/{}", user_id, project_id);
+            Ok(HttpResponse::Ok().json(ApiResponse::success((), "Project deleted".to_string())))
+        }
+        Err(e) => {
+            error!("Failed to delete project {}/{}: {:#}", user_id, project_id, e);
+            Ok(error_response::<()>(&e, "PROJECT_OPERATION_FAILED", "Failed to delete project"))
+        }
+    }
+}
+
+/// Lock the source and destination of a fork or rename, in a fixed order so
+/// two opposite renames cannot deadlock
+async fn lock_pair(
+    locks: &ProjectLocks,
+    user_id: &str,
+    project_id: &str,
+    new_project_id: &str,
+) -> Result<(ProjectGuard, ProjectGuard), ServiceError> {
+    let (first, second) = if project_id <= new_project_id {
+        (project_id, new_project_id)
+    } else {
+        (new_project_id, project_id)
+    };
+    if first == second {
+        return Err(ServiceError::ProjectExists {
+            project: format!("{}/{}", user_id, new_project_id),
+        });
+    }
+    let first = locks.claim_deploy(user_id, first).lock().await?;
+    let second = locks.claim_deploy(user_id, second).lock().await?;
+    Ok((first, second))
+}
+
+/// Disk usage and garbage collection totals, for holders of `ADMIN_TOKEN`
+pub async fn admin_stats_handler(
+    req: HttpRequest,
+    reaper: web::Data<Reaper>,
+) -> Result<HttpResponse> {
+    let expected = env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty());
+    let provided = req
+        .headers()
+        .get("X-Admin-Token")
+        .and_then(|value| value.to_str().ok());
+
+    match (expected, provided) {
+        (Some(expected), Some(provided)) if admin_token_matches(&expected, provided) => Ok(HttpResponse::Ok().json(
+            ApiResponse::success(reaper.stats(), "Storage statistics".to_string()),
+        )),
+        _ => Ok(error_response::<()>(
+            &ServiceError::AdminAccessDenied.into(),
+            "ADMIN_ACCESS_DENIED",
+            "Admin access denied",
+        )),
+    }
+}
+
+/// Compare digests in constant time so neither the token's contents nor its
+/// length leak through response timing
+fn admin_token_matches(expected: &str, provided: &str) -> bool {
+    Sha256::digest(expected).ct_eq(&Sha256::digest(provided)).into()
+}
+
+pub async fn deploy_handler(
+    http: HttpRequest,
+    user: AuthenticatedUser,
+    req: web::Json<DeployRequest>,
+    locks: web::Data<ProjectLocks>,
+    limiter: web::Data<RateLimiter>,
+) -> Result<HttpResponse> {
     info!("Deployment request received for project: {}", req.project_id);
 
-    match deploy_contract(&req.user_id, &req.project_id, req.account_id.as_deref()).await {
+    let client_ip = limiter.client_ip(&http);
+    let result = async {
+        let user_id = user.resolve(req.user_id.as_ref())?;
+        // Wait for any in-flight build so the deployed WASM is complete
+        let _guard = locks.claim_deploy(&user_id, &req.project_id).lock().await?;
+        // The funding is reserved up front and given back only if it was never sent
+        let funding = projected_funding(&user_id, &req.project_id)?;
+        let mut reservation = limiter.reserve_deploy(Some(&user_id), client_ip.as_deref(), funding)?;
+        deploy_contract(
+            &user_id,
+            &req.project_id,
+            req.account_id.as_deref(),
+            funding,
+            &mut || reservation.spend(),
+        )
+        .await
+    }
+    .await;
+
+    match result {
         Ok(deploy_result) => {
             info!("Deployment completed for project: {}", req.project_id);
             Ok(HttpResponse::Ok().json(ApiResponse::success(
@@ -60,23 +550,39 @@ pub async fn deploy_handler(req: web::Json<DeployRequest>) -> Result<HttpRespons
         }
         Err(e) => {
             error!("Deployment failed for project {}: {}", req.project_id, e);
-            Ok(HttpResponse::InternalServerError().json(
-                ApiResponse::<DeployResponse>::error(
-                    "DEPLOYMENT_FAILED".to_string(),
-                    "Failed to deploy contract".to_string(),
-                    Some(e.to_string()),
-                ),
+            Ok(error_response::<DeployResponse>(
+                &e,
+                "DEPLOYMENT_FAILED",
+                "Failed to deploy contract",
             ))
         }
     }
 }
 
-pub async fn method_call_handler(req: web::Json<MethodCallRequest>) -> Result<HttpResponse> {
+pub async fn method_call_handler(
+    http: HttpRequest,
+    user: AuthenticatedUser,
+    req: web::Json<MethodCallRequest>,
+    limiter: web::Data<RateLimiter>,
+) -> Result<HttpResponse> {
     info!(
         "Method call request received for contract: {} method: {}",
         req.contract_address, req.method_name
     );
 
+    // Change calls are signed, and paid for, by the operator account
+    if req.method_type == "call" {
+        let client_ip = limiter.client_ip(&http);
+        if let Err(e) = limiter.acquire(LimitedAction::Call, user.user_id(), client_ip.as_deref()) {
+            info!("Rejecting call to {}: {}", req.contract_address, e);
+            return Ok(error_response::<MethodCallResponse>(
+                &e.into(),
+                "METHOD_CALL_FAILED",
+                "Failed to call contract method",
+            ));
+        }
+    }
+
     match call_contract_method(
         &req.contract_address,
         &req.method_name,
@@ -107,4 +613,234 @@ pub async fn method_call_handler(req: web::Json<MethodCallRequest>) -> Result<Ht
             ))
         }
     }
-}
\ No newline at end of file
+}
+
+fn sse_frame(event: &CompileEvent) -> String {
+    let data = serde_json::to_string(event).unwrap_or_else(|_| "{}".to_string());
+    format!("event: {}\ndata: {}\n\n", event.name(), data)
+}
+
+/// Build an error response, using the specific code and status of a
+/// `ServiceError` when the failure carries one.
+fn error_response<T: serde::Serialize>(error: &anyhow::Error, code: &str, message: &str) -> HttpResponse {
+    let (status, api_error) = to_api_error(error, code, message);
+    let mut response = HttpResponse::build(status);
+    if let Some(retry_after) = error
+        .downcast_ref::<ServiceError>()
+        .and_then(ServiceError::retry_after_secs)
+    {
+        response.insert_header(("Retry-After", retry_after.to_string()));
+    }
+    response.json(ApiResponse::<T>::error(
+        api_error.code,
+        api_error.message,
+        api_error.details,
+    ))
+}
+
+/// Verify the caller's bearer token and make its user available to the
+/// handlers behind this middleware as `AuthenticatedUser`
+pub async fn authenticate<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>> {
+    let authorization = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    let result = match req.app_data::<web::Data<Authenticator>>() {
+        Some(authenticator) => authenticator.authenticate(authorization),
+        None => Err(ServiceError::Unauthenticated {
+            reason: "authentication is not configured on this server".to_string(),
+        }),
+    };
+
+    match result {
+        Ok(user) => {
+            req.extensions_mut().insert(user);
+            Ok(next.call(req).await?.map_into_left_body())
+        }
+        Err(e) => {
+            info!("Rejected unauthenticated request to {}: {}", req.path(), e);
+            let mut response = error_response::<()>(&e.into(), "UNAUTHENTICATED", "Authentication failed");
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+/// Report a JSON body that failed to parse, e.g. with an invalid `project_id`
+pub fn json_error_handler(error: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let message = match &error {
+        JsonPayloadError::Deserialize(e) => e.to_string(),
+        other => other.to_string(),
+    };
+    InternalError::from_response(error, rejected_request(message)).into()
+}
+
+/// Report a URL whose `user_id` or `project_id` segment is not a valid id
+pub fn path_error_handler(error: PathError, _req: &HttpRequest) -> actix_web::Error {
+    let message = match &error {
+        PathError::Deserialize(e) => e.to_string(),
+        other => other.to_string(),
+    };
+    InternalError::from_response(error, rejected_request(message)).into()
+}
+
+fn rejected_request(message: String) -> HttpResponse {
+    let (code, message, details) = match identifier_error(&message) {
+        Some(identifier) => ("INVALID_IDENTIFIER", identifier.to_string(), None),
+        None => ("INVALID_REQUEST", "Invalid request".to_string(), Some(message)),
+    };
+    HttpResponse::BadRequest().json(ApiResponse::<()>::error(code.to_string(), message, details))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::auth::tests::{authenticator, token, SECRET};
+    use crate::services::rate_limits::tests::limiter;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App};
+
+    fn bearer(user_id: &str) -> (header::HeaderName, String) {
+        (
+            header::AUTHORIZATION,
+            format!("Bearer {}", token(SECRET, user_id, "authenticated", 3600)),
+        )
+    }
+
+    macro_rules! app {
+        () => {
+            test::init_service(
+                App::new()
+                    .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+                    .app_data(web::PathConfig::default().error_handler(path_error_handler))
+                    .app_data(web::Data::new(ProjectLocks::new()))
+                    .app_data(web::Data::new(authenticator()))
+                    .app_data(web::Data::new(limiter(None)))
+                    .service(
+                        web::scope("")
+                            .wrap(from_fn(authenticate))
+                            .route("/projects/{user_id}", web::get().to(list_projects_handler))
+                            .route("/projects/{user_id}/{project_id}", web::get().to(project_handler))
+                            .route("/deploy", web::post().to(deploy_handler)),
+                    ),
+            )
+        };
+    }
+
+    #[actix_rt::test]
+    async fn test_invalid_identifiers_are_rejected() {
+        let app = app!().await;
+
+        let request = test::TestRequest::get()
+            .uri("/projects/alice/..%2F..%2Fetc")
+            .insert_header(bearer("alice"))
+            .to_request();
+        let response: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(response["error"]["code"], "INVALID_IDENTIFIER");
+
+        let request = test::TestRequest::post()
+            .uri("/deploy")
+            .insert_header(bearer("alice"))
+            .set_json(serde_json::json!({ "user_id": "../../etc", "project_id": "counter" }))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"]["code"], "INVALID_IDENTIFIER");
+        let message = body["message"].as_str().unwrap();
+        assert!(message.starts_with(crate::errors::INVALID_IDENTIFIER), "{}", message);
+        assert!(message.contains("user_id") && !message.contains(" at line "), "{}", message);
+    }
+
+    #[actix_rt::test]
+    async fn test_requests_act_for_the_token_user() {
+        let app = app!().await;
+
+        let request = test::TestRequest::get().uri("/projects/alice").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key(header::WWW_AUTHENTICATE));
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"]["code"], "UNAUTHENTICATED");
+
+        let request = test::TestRequest::get()
+            .uri("/projects/alice")
+            .insert_header(bearer("mallory"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = test::TestRequest::post()
+            .uri("/deploy")
+            .insert_header(bearer("mallory"))
+            .set_json(serde_json::json!({ "user_id": "alice", "project_id": "counter" }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["error"]["code"], "USER_MISMATCH");
+
+        let request = test::TestRequest::get()
+            .uri("/projects/no-such-user-in-tests")
+            .insert_header(bearer("no-such-user-in-tests"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_full_queue_keeps_compile_quota() {
+        let queue = BuildQueue::new(1, 0);
+        let limiter = limiter(None);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(JobRegistry::new(queue.clone())))
+                .app_data(web::Data::new(queue.clone()))
+                .app_data(web::Data::new(ProjectLocks::new()))
+                .app_data(web::Data::new(authenticator()))
+                .app_data(web::Data::new(limiter.clone()))
+                .service(
+                    web::scope("")
+                        .wrap(from_fn(authenticate))
+                        .route("/compile", web::post().to(compile_handler)),
+                ),
+        )
+        .await;
+        let running = queue.enqueue().unwrap().acquire().await;
+
+        // More attempts than the per-user quota allows, all turned away by the queue
+        for _ in 0..3 {
+            let request = test::TestRequest::post()
+                .uri("/compile")
+                .insert_header(bearer("alice"))
+                .set_json(serde_json::json!({ "project_id": "counter" }))
+                .to_request();
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            let body: serde_json::Value = test::read_body_json(response).await;
+            assert_eq!(body["error"]["code"], "BUILD_QUEUE_FULL");
+        }
+        drop(running);
+
+        let alice = UserId::try_from("alice".to_string()).unwrap();
+        for _ in 0..2 {
+            assert!(limiter.acquire(LimitedAction::Compile, Some(&alice), None).is_ok());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_rate_limited_response_says_when_to_retry() {
+        let error = ServiceError::RateLimited {
+            limit: "10 deploys per day per user".to_string(),
+            reset_at: chrono::Utc::now() + chrono::Duration::minutes(5),
+        };
+        let response = error_response::<()>(&error.into(), "DEPLOYMENT_FAILED", "Failed to deploy contract");
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = response.headers().get(header::RETRY_AFTER).unwrap().to_str().unwrap().parse().unwrap();
+        assert!((290..=300).contains(&retry_after));
+    }
+}
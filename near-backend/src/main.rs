@@ -1,60 +1,142 @@
 use actix_cors::Cors;
-use actix_web::{middleware::Logger, web, App, HttpServer};
+use actix_web::middleware::{from_fn, Logger};
+use actix_web::{web, App, HttpServer};
 use env_logger::Env;
 use log::{info, warn, error};
 use std::path::Path;
-use std::process::Command;
 use std::env;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
 
+mod errors;
 mod handlers;
 mod models;
 mod services;
 mod utils;
 
-use handlers::{compile_handler, deploy_handler, health_handler, method_call_handler};
+use handlers::{
+    admin_stats_handler, artifact_handler, authenticate, cancel_job_handler, compile_handler, delete_project_handler, deploy_handler,
+    fork_project_handler, health_handler, job_events_handler, job_status_handler, json_error_handler,
+    list_projects_handler, path_error_handler, method_call_handler, project_handler, rename_project_handler,
+    template_handler, templates_handler, toolchains_handler,
+};
+use models::BuildMode;
+use services::auth::Authenticator;
+use services::build_mode::BuildCommand;
+use services::compilation::copy_project;
+use services::build_queue::BuildQueue;
+use services::jobs::JobRegistry;
+use services::dependency_cache::{toolchain_version, DependencyCache};
+use services::diagnostics::CHECK_ARGS;
+use services::process::run_command;
+use services::project_locks::ProjectLocks;
+use services::rate_limits::RateLimiter;
+use services::reaper::Reaper;
+use services::sandbox::{restrict_env, Sandbox};
+use services::storage_cost::StoragePricing;
+use services::toolchains::{ToolchainCatalog, VersionSelection, TEMPLATE_BASE_PROJECT};
+
+/// Default wall-clock limit for building the base project, which compiles
+/// the full near-sdk dependency tree from scratch
+const DEFAULT_BASE_PROJECT_TIMEOUT_SECS: u64 = 1200;
+
+/// Wall-clock limit for each base project step, from `BASE_PROJECT_TIMEOUT_SECS`
+fn base_project_timeout() -> Duration {
+    let secs = env::var("BASE_PROJECT_TIMEOUT_SECS")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_BASE_PROJECT_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
 
 async fn initialize_base_project() -> std::io::Result<()> {
-    let base_project_path = Path::new("base_project");
+    let base_project_path = Path::new(TEMPLATE_BASE_PROJECT);
     
     if !base_project_path.exists() {
         info!("Base project not found, creating it...");
         
-        let output = Command::new("cargo")
-            .arg("near")
-            .arg("new")
-            .arg("base_project")
-            .output()?;
-            
+        let mut command = Command::new("cargo");
+        restrict_env(&mut command);
+        command.arg("near").arg("new").arg(TEMPLATE_BASE_PROJECT);
+
+        let output = run_command(command, Instant::now() + base_project_timeout(), &|_| {})
+            .await
+            .map_err(|e| {
+                error!("Failed to create base project: {:#}", e);
+                std::io::Error::other(e)
+            })?;
         if !output.status.success() {
-            error!("Failed to create base project: {}", String::from_utf8_lossy(&output.stderr));
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to create base project"
-            ));
+            error!("Failed to create base project: {}", output.stderr);
+            return Err(std::io::Error::other("Failed to create base project"));
         }
         
         info!("Base project created successfully");
     }
+
+    // One base project per configured toolchain and near-sdk combination,
+    // each derived from the template
+    for versions in ToolchainCatalog::from_env().combinations() {
+        let path = versions.base_project_path();
+        if !path.exists() {
+            info!("Creating base project {:?}", path);
+            copy_project(base_project_path, &path).map_err(std::io::Error::other)?;
+            versions.apply(&path).map_err(std::io::Error::other)?;
+        }
+        build_base_project(&versions).await?;
+    }
     
-    // Build the base project to cache dependencies
+    Ok(())
+}
+
+/// Build a base project to cache its dependencies
+async fn build_base_project(versions: &VersionSelection) -> std::io::Result<()> {
+    let base_project_path = &versions.base_project_path();
     let target_dir = base_project_path.join("target");
     if !target_dir.exists() {
-        info!("Building base project to cache dependencies...");
+        info!("Building base project {:?} to cache dependencies...", base_project_path);
         
-        let output = Command::new("cargo")
-            .arg("near")
-            .arg("build")
-            .arg("non-reproducible-wasm")
-            .current_dir(base_project_path)
-            .output()?;
-            
-        if output.status.success() {
-            info!("Base project built successfully, dependencies cached");
-        } else {
-            warn!("Base project build failed, but continuing: {}", String::from_utf8_lossy(&output.stderr));
+        let build = BuildCommand::from_env(BuildMode::default());
+        let mut command = Command::new("cargo");
+        restrict_env(&mut command);
+        command
+            .args(build.args())
+            .envs(build.envs(base_project_path).map_err(std::io::Error::other)?)
+            .current_dir(base_project_path);
+
+        let deadline = Instant::now() + base_project_timeout();
+        match run_command(command, deadline, &|_| {}).await {
+            Ok(output) if output.status.success() => {
+                info!("Base project built successfully");
+            }
+            Ok(output) => {
+                warn!("Base project build failed, but continuing: {}", output.stderr);
+            }
+            Err(e) => {
+                warn!("Base project build failed, but continuing: {}", e);
+            }
+        }
+
+        // Compile requests type-check for diagnostics, so warm those units too
+        let mut command = Command::new("cargo");
+        restrict_env(&mut command);
+        command.args(CHECK_ARGS).current_dir(base_project_path);
+        if let Err(e) = run_command(command, deadline, &|_| {}).await {
+            warn!("Base project check failed, but continuing: {}", e);
         }
     } else {
-        info!("Base project already built, dependencies available");
+        info!("Base project {:?} already built, dependencies available", base_project_path);
+    }
+
+    // New projects are seeded from this entry
+    if target_dir.exists() {
+        let stored = match toolchain_version(versions).await {
+            Ok(version) => DependencyCache::from_env().store(base_project_path, &version),
+            Err(e) => Err(e),
+        };
+        match stored {
+            Ok(()) => info!("Dependency cache ready"),
+            Err(e) => warn!("Failed to cache base project dependencies: {:#}", e),
+        }
     }
     
     Ok(())
@@ -68,7 +150,25 @@ async fn main() -> std::io::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
     info!("Starting NEAR Playground Backend Server");
+
+    // Builds run untrusted code, so refuse to serve them unconfined
+    Sandbox::from_env().ensure_available().await.map_err(|e| {
+        error!("Build sandbox unavailable: {:#}", e);
+        std::io::Error::other(e)
+    })?;
     
+    // Deploy funding is derived from these, so a typo must not go unnoticed
+    StoragePricing::from_env().map_err(|e| {
+        error!("Invalid storage pricing: {:#}", e);
+        std::io::Error::other(e)
+    })?;
+
+    // Likewise for the limits that cap builds and the NEAR deployments fund
+    let limiter = RateLimiter::from_env().map_err(|e| {
+        error!("Invalid rate limits: {:#}", e);
+        std::io::Error::other(e)
+    })?;
+
     // Initialize base project on startup
     initialize_base_project().await?;
 
@@ -79,7 +179,24 @@ async fn main() -> std::io::Result<()> {
 
     info!("Starting NEAR Playground Backend on {}", bind_address);
 
-    HttpServer::new(|| {
+    // Shared across workers so builds are limited server-wide and any
+    // worker can report on or cancel a job
+    let queue = BuildQueue::from_env();
+    let jobs = web::Data::new(JobRegistry::new(queue.clone()));
+    let queue = web::Data::new(queue);
+    let locks = web::Data::new(ProjectLocks::new());
+
+    // Frees disk held by idle projects in the background
+    let reaper = Reaper::from_env();
+    actix_web::rt::spawn(reaper.clone().run(locks.get_ref().clone()));
+    let reaper = web::Data::new(reaper);
+    let authenticator = web::Data::new(Authenticator::from_env());
+    actix_web::rt::spawn(limiter.clone().run());
+    let limiter = web::Data::new(limiter);
+
+    let final_flush = limiter.clone();
+
+    let served = HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
             .allow_any_method()
@@ -89,12 +206,51 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(cors)
             .wrap(Logger::default())
+            .app_data(jobs.clone())
+            .app_data(queue.clone())
+            .app_data(locks.clone())
+            .app_data(reaper.clone())
+            .app_data(authenticator.clone())
+            .app_data(limiter.clone())
+            .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+            .app_data(web::PathConfig::default().error_handler(path_error_handler))
             .route("/health", web::get().to(health_handler))
-            .route("/compile", web::post().to(compile_handler))
-            .route("/deploy", web::post().to(deploy_handler))
-            .route("/method-call", web::post().to(method_call_handler))
+            .route("/admin/stats", web::get().to(admin_stats_handler))
+            .route("/templates", web::get().to(templates_handler))
+            .route("/templates/{id}", web::get().to(template_handler))
+            .route("/toolchains", web::get().to(toolchains_handler))
+            // Everything below acts for a user and needs a valid access token
+            .service(
+                web::scope("")
+                    .wrap(from_fn(authenticate))
+                    .route("/compile", web::post().to(compile_handler))
+                    .route("/jobs/{id}", web::get().to(job_status_handler))
+                    .route("/jobs/{id}", web::delete().to(cancel_job_handler))
+                    .route("/jobs/{id}/events", web::get().to(job_events_handler))
+                    .route("/projects/{user_id}", web::get().to(list_projects_handler))
+                    .route("/projects/{user_id}/{project_id}", web::get().to(project_handler))
+                    .route("/projects/{user_id}/{project_id}", web::delete().to(delete_project_handler))
+                    .route(
+                        "/projects/{user_id}/{project_id}/fork",
+                        web::post().to(fork_project_handler),
+                    )
+                    .route(
+                        "/projects/{user_id}/{project_id}/rename",
+                        web::post().to(rename_project_handler),
+                    )
+                    .route(
+                        "/projects/{user_id}/{project_id}/artifacts/{kind}",
+                        web::get().to(artifact_handler),
+                    )
+                    .route("/deploy", web::post().to(deploy_handler))
+                    .route("/method-call", web::post().to(method_call_handler))
+            )
     })
     .bind(&bind_address)?
     .run()
-    .await
+    .await;
+
+    // Keep counters changed since the last periodic flush
+    final_flush.flush().await;
+    served
 }
\ No newline at end of file
@@ -1,16 +1,190 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Deref;
 
-#[derive(Debug, Deserialize)]
+use crate::errors::ServiceError;
+
+/// Longest user or project id accepted
+const MAX_IDENTIFIER_LEN: usize = 128;
+
+/// Check an id that becomes a single directory name under `projects/`
+fn validate_identifier(kind: &'static str, value: String) -> Result<String, ServiceError> {
+    let reason = if value.is_empty() {
+        Some("must not be empty")
+    } else if value.len() > MAX_IDENTIFIER_LEN {
+        Some("must be at most 128 characters")
+    } else if !value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        Some("may only contain letters, digits, '-', '_' and '.'")
+    } else if value.starts_with('.') {
+        Some("must not start with '.'")
+    } else {
+        None
+    };
+
+    match reason {
+        Some(reason) => Err(ServiceError::InvalidIdentifier { kind, value, reason }),
+        None => Ok(value),
+    }
+}
+
+/// A user id that is safe to use as a directory name
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub struct UserId(String);
+
+/// A project id that is safe to use as a directory name
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ProjectId(String);
+
+impl TryFrom<String> for UserId {
+    type Error = ServiceError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        validate_identifier("user_id", value).map(UserId)
+    }
+}
+
+impl TryFrom<String> for ProjectId {
+    type Error = ServiceError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        validate_identifier("project_id", value).map(ProjectId)
+    }
+}
+
+impl UserId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for UserId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl ProjectId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for ProjectId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for ProjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct CompileRequest {
-    pub user_id: String,
-    pub project_id: String,
-    pub code: String,
+    /// Taken from the access token; if given it must match the token's user
+    #[serde(default)]
+    pub user_id: Option<UserId>,
+    pub project_id: ProjectId,
+    /// Contents of `src/lib.rs`, kept for single-file clients
+    #[serde(default)]
+    pub code: Option<String>,
+    /// Project files keyed by path relative to the project root
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// Paths relative to the project root to remove before building
+    #[serde(default)]
+    pub deleted_files: Vec<String>,
+    /// Changes to `[dependencies]` in the project's Cargo.toml
+    #[serde(default)]
+    pub dependencies: Vec<DependencyChange>,
+    /// Return a job id immediately instead of waiting for the build
+    #[serde(default, rename = "async")]
+    pub run_async: bool,
+    #[serde(default)]
+    pub build_mode: BuildMode,
+    /// Passes to run on the WASM after cargo-near has built it
+    #[serde(default)]
+    pub optimization: OptimizationLevel,
+    /// rustup toolchain from `GET /toolchains`; omitted keeps the project's current one
+    #[serde(default)]
+    pub toolchain: Option<String>,
+    /// near-sdk version from `GET /toolchains`; omitted keeps the project's current one
+    #[serde(default)]
+    pub near_sdk_version: Option<String>,
+    /// Template from `GET /templates` a new project starts from; ignored once the project exists
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Which `cargo near build` flavour produces the contract
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BuildMode {
+    #[default]
+    #[serde(rename = "non-reproducible-wasm")]
+    NonReproducible,
+    #[serde(rename = "reproducible-wasm")]
+    Reproducible,
+}
+
+impl BuildMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuildMode::NonReproducible => "non-reproducible-wasm",
+            BuildMode::Reproducible => "reproducible-wasm",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyChange {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Remove the dependency instead of adding or updating it
+    #[serde(default)]
+    pub remove: bool,
+}
+
+impl CompileRequest {
+    /// All files to write, with `code` mapped onto `src/lib.rs` unless the
+    /// file tree already provides it
+    pub fn project_files(&self) -> BTreeMap<String, String> {
+        let mut files = self.files.clone();
+        if let Some(code) = &self.code {
+            files
+                .entry("src/lib.rs".to_string())
+                .or_insert_with(|| code.clone());
+        }
+        files
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct DeployRequest {
-    pub user_id: String,
-    pub project_id: String,
+    /// Taken from the access token; if given it must match the token's user
+    #[serde(default)]
+    pub user_id: Option<UserId>,
+    pub project_id: ProjectId,
     pub account_id: Option<String>,
 }
 
@@ -30,14 +204,14 @@ pub struct ApiResponse<T> {
     pub error: Option<ApiError>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ApiError {
     pub code: String,
     pub message: String,
     pub details: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompileResponse {
     pub success: bool,
     pub exit_code: i32,
@@ -45,17 +219,312 @@ pub struct CompileResponse {
     pub stderr: String,
     pub details: CompileDetails,
     pub abi: Option<serde_json::Value>,
+    pub refused_dependencies: Vec<RefusedDependency>,
+    /// Structured compiler messages, warnings included, from every build
+    pub diagnostics: Vec<Diagnostic>,
+    /// Served from the compile result cache without running cargo
+    #[serde(default)]
+    pub cache_hit: bool,
+    /// Toolchain and tooling that produced the WASM, for later verification
+    #[serde(default)]
+    pub build_environment: Option<BuildEnvironment>,
+    /// Structure of the built WASM: exports, imports, memory and size breakdown
+    #[serde(default)]
+    pub wasm_report: Option<WasmReport>,
+    /// Disagreements between the ABI and the WASM's exports
+    #[serde(default)]
+    pub abi_warnings: Vec<AbiWarning>,
+    /// Storage staking a deployment of this WASM locks up
+    #[serde(default)]
+    pub storage_estimate: Option<StorageEstimate>,
+    #[serde(default)]
+    pub optimization: Option<OptimizationReport>,
+    /// Versions the project was pinned to that are no longer installed
+    #[serde(default)]
+    pub version_changes: Vec<VersionChange>,
 }
 
-#[derive(Debug, Serialize)]
+/// yoctoNEAR amounts are strings since they overflow JSON numbers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageEstimate {
+    pub code_bytes: u64,
+    pub account_overhead_bytes: u64,
+    pub total_bytes: u64,
+    pub price_per_byte: String,
+    pub storage_cost: String,
+    pub storage_cost_near: String,
+    /// Storage cost plus the configured margin and proof transfer, what a deploy transfers
+    pub recommended_funding: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbiWarning {
+    /// `missing_export`, `unexpected_export` or `private_function`
+    pub kind: String,
+    pub function: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmReport {
+    pub total_size: u64,
+    pub exports: Vec<WasmExport>,
+    /// Host functions and other items the contract imports, e.g. `env.storage_write`
+    pub imports: Vec<WasmImport>,
+    pub memory: Option<WasmLimits>,
+    pub table: Option<WasmLimits>,
+    pub data_segments: usize,
+    pub data_size: u64,
+    pub sections: Vec<WasmSectionSize>,
+    pub function_count: usize,
+    /// Largest function bodies first, capped to the biggest few
+    pub largest_functions: Vec<WasmFunctionSize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmExport {
+    pub name: String,
+    pub kind: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmImport {
+    pub module: String,
+    pub name: String,
+    pub kind: String,
+}
+
+/// Initial and maximum size in pages (memory) or elements (table)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmLimits {
+    pub initial: u64,
+    pub maximum: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmSectionSize {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmFunctionSize {
+    pub index: u32,
+    /// From the export or `name` section; stripped builds leave most unnamed
+    pub name: Option<String>,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildEnvironment {
+    /// The build that actually ran: `non-reproducible-wasm`,
+    /// `reproducible-wasm` (cargo-near's Docker image) or `pinned-local`
+    /// (a reproducible request built locally, which cannot be verified)
+    pub mode: String,
+    /// `local` (pinned toolchain, remapped paths) or `docker` (cargo-near's pinned image)
+    pub backend: Option<String>,
+    /// `rustc -vV` release line
+    pub toolchain: String,
+    /// near-sdk version resolved in Cargo.lock
+    #[serde(default)]
+    pub near_sdk: Option<String>,
+    pub cargo_near: Option<String>,
+    pub image: Option<String>,
+    pub image_digest: Option<String>,
+    /// Whether the build refused to update `Cargo.lock`
+    pub locked: bool,
+}
+
+/// Post-build optimization applied to the contract WASM
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizationLevel {
+    /// Keep cargo-near's output as is
+    #[default]
+    None,
+    /// Drop debug info, names and other custom sections
+    Strip,
+    /// Strip, then remove functions, globals and data nothing references
+    Size,
+}
+
+impl OptimizationLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OptimizationLevel::None => "none",
+            OptimizationLevel::Strip => "strip",
+            OptimizationLevel::Size => "size",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizationReport {
+    pub level: OptimizationLevel,
+    pub original_size: u64,
+    pub optimized_size: u64,
+    /// Passes whose output was kept; empty when the WASM was left unchanged
+    pub passes: Vec<String>,
+    /// Whether the final WASM fits NEAR's maximum contract size
+    pub within_size_limit: bool,
+    /// Why the optimized output was discarded, if it was
+    pub note: Option<String>,
+}
+
+/// A compiler message in a form the editor can underline and fix
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub level: String,
+    pub code: Option<String>,
+    pub message: String,
+    pub spans: Vec<DiagnosticSpan>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<DiagnosticSuggestion>,
+    pub rendered: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticSpan {
+    pub file: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub column_start: usize,
+    pub column_end: usize,
+    pub is_primary: bool,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticSuggestion {
+    pub message: String,
+    pub span: DiagnosticSpan,
+    pub replacement: String,
+    pub applicability: Option<String>,
+}
+
+/// A toolchain or near-sdk version a project used that the server no longer
+/// offers, replaced by the server default
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionChange {
+    pub component: String,
+    pub previous: String,
+    pub current: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefusedDependency {
+    pub name: String,
+    pub version: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompileDetails {
     pub status: String,
     pub compilation_time: f64,
     pub project_path: String,
     pub wasm_size: Option<u64>,
+    /// base58 of the WASM's sha256, as NEAR reports an account's code hash
+    #[serde(default)]
+    pub code_hash: Option<String>,
+    /// Whether a post-build optimization pass rewrote the WASM
     pub optimized: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+    Superseded,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Superseded => "superseded",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed
+                | JobStatus::Failed
+                | JobStatus::Cancelled
+                | JobStatus::Superseded
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Events streamed to clients while a compile job runs
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CompileEvent {
+    Status { status: JobStatus },
+    Output { stream: OutputStream, line: String },
+    Progress { message: String },
+    Completed { result: Box<CompileResponse> },
+    Failed { error: ApiError },
+    Cancelled,
+    Superseded,
+}
+
+impl CompileEvent {
+    /// SSE event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompileEvent::Status { .. } => "status",
+            CompileEvent::Output { .. } => "output",
+            CompileEvent::Progress { .. } => "progress",
+            CompileEvent::Completed { .. } => "completed",
+            CompileEvent::Failed { .. } => "failed",
+            CompileEvent::Cancelled => "cancelled",
+            CompileEvent::Superseded => "superseded",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            CompileEvent::Completed { .. }
+                | CompileEvent::Failed { .. }
+                | CompileEvent::Cancelled
+                | CompileEvent::Superseded
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobStatusResponse {
+    pub job_id: String,
+    pub user_id: String,
+    pub project_id: String,
+    pub status: JobStatus,
+    /// 1-based place in the build queue while the job is waiting
+    pub queue_position: Option<usize>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub result: Option<CompileResponse>,
+    pub error: Option<ApiError>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct DeployResponse {
     pub success: bool,
@@ -73,6 +542,9 @@ pub struct DeployDetails {
     pub block_height: u64,
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub deployer_account: String,
+    /// yoctoNEAR transferred to the new contract account
+    pub funded_amount: String,
+    pub storage_estimate: StorageEstimate,
 }
 
 #[derive(Debug, Serialize)]
@@ -85,6 +557,91 @@ pub struct MethodCallResponse {
     pub error: Option<String>,
 }
 
+/// Outcome of a project's most recent compile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastBuild {
+    pub success: bool,
+    pub finished_at: chrono::DateTime<chrono::Utc>,
+    pub compilation_time: f64,
+    pub build_mode: BuildMode,
+    pub wasm_size: Option<u64>,
+    pub code_hash: Option<String>,
+    pub cache_hit: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectSummary {
+    pub project_id: String,
+    /// Last modification of any source file
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_build: Option<LastBuild>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectDetails {
+    #[serde(flatten)]
+    pub project: ProjectSummary,
+    /// Source files keyed by path relative to the project root
+    pub files: BTreeMap<String, String>,
+}
+
+/// Disk held under `projects/` by one user
+#[derive(Debug, Clone, Serialize)]
+pub struct UserDiskUsage {
+    pub user_id: String,
+    pub projects: usize,
+    pub bytes: u64,
+}
+
+/// Garbage collection totals since the server started, with usage as of the last run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReaperStats {
+    pub runs: u64,
+    pub last_run: Option<chrono::DateTime<chrono::Utc>>,
+    /// `target` directories removed from idle projects
+    pub targets_evicted: u64,
+    /// Projects removed entirely after `PROJECT_MAX_AGE_DAYS`
+    pub projects_deleted: u64,
+    pub bytes_freed: u64,
+    pub total_bytes: u64,
+    pub quota_bytes: Option<u64>,
+    pub users: Vec<UserDiskUsage>,
+}
+
+/// Body of the fork and rename endpoints
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectIdRequest {
+    pub new_project_id: ProjectId,
+}
+
+/// A starter project; `files` is only filled in when a single template is requested
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTemplate {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    pub difficulty: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Added to the project's Cargo.toml, subject to the dependency allowlist
+    #[serde(default)]
+    pub dependencies: Vec<DependencyChange>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+}
+
+/// Versions a compile request can choose from; `None` defaults mean the
+/// server does not manage that version
+#[derive(Debug, Serialize)]
+pub struct ToolchainsResponse {
+    pub toolchains: Vec<String>,
+    pub near_sdk_versions: Vec<String>,
+    pub default_toolchain: Option<String>,
+    pub default_near_sdk_version: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct HealthResponse {
     pub status: String,
@@ -110,4 +667,49 @@ impl<T> ApiResponse<T> {
             error: Some(ApiError { code, message, details }),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{identifier_error, INVALID_IDENTIFIER};
+
+    #[test]
+    fn test_identifiers_reject_traversal() {
+        assert!(ProjectId::try_from("counter-2_v1.0".to_string()).is_ok());
+        assert!(UserId::try_from("3f1c2a9e-0b7d-4c1e-9a55-2d8f6e4b1c70".to_string()).is_ok());
+
+        for invalid in ["", ".", "..", "../../etc", "a/b", "a\\b", ".hidden", "a\0b", "name with space"] {
+            assert!(
+                matches!(
+                    ProjectId::try_from(invalid.to_string()),
+                    Err(ServiceError::InvalidIdentifier { .. })
+                ),
+                "{:?} should be rejected",
+                invalid
+            );
+        }
+        assert!(UserId::try_from("a".repeat(MAX_IDENTIFIER_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_requests_reject_invalid_identifiers() {
+        let request: Result<DeployRequest, _> =
+            serde_json::from_str(r#"{"user_id": "../../etc", "project_id": "counter", "account_id": null}"#);
+        let error = request.unwrap_err().to_string();
+        assert_eq!(
+            identifier_error(&error),
+            Some(format!("{}: user_id '../../etc' may only contain letters, digits, '-', '_' and '.'", INVALID_IDENTIFIER).as_str()),
+            "{}",
+            error
+        );
+
+        let request: DeployRequest = serde_json::from_value(serde_json::json!({
+            "user_id": "alice",
+            "project_id": "counter",
+            "account_id": null
+        }))
+        .unwrap();
+        assert_eq!(&*request.project_id, "counter");
+    }
+}
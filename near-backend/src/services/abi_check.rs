@@ -0,0 +1,131 @@
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use crate::models::{AbiWarning, WasmReport};
+
+/// Functions near-sdk exports without listing them in the ABI
+const SDK_EXPORTS: [&str; 1] = ["contract_source_metadata"];
+
+/// Compare the ABI's functions with the functions the WASM exports. Every
+/// ABI function should be callable and every exported function should be
+/// described; `#[private]` methods are flagged because only the contract
+/// account itself can call them.
+pub fn check_abi_exports(abi: &Value, report: &WasmReport) -> Vec<AbiWarning> {
+    let functions = abi
+        .pointer("/body/functions")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    let exported: BTreeSet<&str> = report
+        .exports
+        .iter()
+        .filter(|export| export.kind == "function")
+        .map(|export| export.name.as_str())
+        .collect();
+    let mut described = BTreeSet::new();
+    let mut warnings = Vec::new();
+
+    for function in functions {
+        let Some(name) = function.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        described.insert(name);
+
+        if !exported.contains(name) {
+            warnings.push(AbiWarning {
+                kind: "missing_export".to_string(),
+                function: name.to_string(),
+                message: format!(
+                    "ABI lists '{}' but the WASM does not export it, so calls to it will fail",
+                    name
+                ),
+            });
+        }
+
+        let is_private = function
+            .get("modifiers")
+            .and_then(Value::as_array)
+            .is_some_and(|modifiers| modifiers.iter().any(|modifier| modifier == "private"));
+        if is_private {
+            warnings.push(AbiWarning {
+                kind: "private_function".to_string(),
+                function: name.to_string(),
+                message: format!(
+                    "'{}' is #[private] and can only be called by the contract account itself",
+                    name
+                ),
+            });
+        }
+    }
+
+    for name in exported {
+        if !described.contains(name) && !SDK_EXPORTS.contains(&name) {
+            warnings.push(AbiWarning {
+                kind: "unexpected_export".to_string(),
+                function: name.to_string(),
+                message: format!(
+                    "The WASM exports '{}' but the ABI does not describe it",
+                    name
+                ),
+            });
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WasmExport;
+
+    #[test]
+    fn test_check_abi_exports() {
+        let abi = serde_json::json!({
+            "schema_version": "0.4.0",
+            "body": {
+                "functions": [
+                    { "name": "get_greeting", "kind": "view" },
+                    { "name": "set_greeting", "kind": "call" },
+                    { "name": "on_callback", "kind": "call", "modifiers": ["private"] }
+                ]
+            }
+        });
+        let export = |name: &str, kind: &str| WasmExport {
+            name: name.to_string(),
+            kind: kind.to_string(),
+        };
+        let report = WasmReport {
+            total_size: 0,
+            exports: vec![
+                export("get_greeting", "function"),
+                export("on_callback", "function"),
+                export("contract_source_metadata", "function"),
+                export("debug_dump", "function"),
+                export("memory", "memory"),
+            ],
+            imports: Vec::new(),
+            memory: None,
+            table: None,
+            data_segments: 0,
+            data_size: 0,
+            sections: Vec::new(),
+            function_count: 0,
+            largest_functions: Vec::new(),
+        };
+
+        let warnings: Vec<_> = check_abi_exports(&abi, &report)
+            .into_iter()
+            .map(|warning| (warning.kind, warning.function))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                ("missing_export".to_string(), "set_greeting".to_string()),
+                ("private_function".to_string(), "on_callback".to_string()),
+                ("unexpected_export".to_string(), "debug_dump".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::errors::ServiceError;
+use crate::models::{ProjectId, UserId};
+
+/// Allowance for filesystems that store modification times coarsely
+const MTIME_SLACK: Duration = Duration::from_secs(2);
+
+/// Build outputs that can be downloaded from a project
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    Wasm,
+    Abi,
+}
+
+impl ArtifactKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "wasm" => Some(ArtifactKind::Wasm),
+            "abi" => Some(ArtifactKind::Abi),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArtifactKind::Wasm => "wasm",
+            ArtifactKind::Abi => "abi",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ArtifactKind::Wasm => "application/wasm",
+            ArtifactKind::Abi => "application/json",
+        }
+    }
+
+    /// File name cargo-near gives this artifact for a crate
+    fn file_name(&self, crate_name: &str) -> String {
+        match self {
+            ArtifactKind::Wasm => format!("{}.wasm", crate_name),
+            ArtifactKind::Abi => format!("{}_abi.json", crate_name),
+        }
+    }
+
+    fn matches(&self, file_name: &str) -> bool {
+        match self {
+            ArtifactKind::Wasm => file_name.ends_with(".wasm"),
+            ArtifactKind::Abi => file_name.ends_with("_abi.json"),
+        }
+    }
+}
+
+/// Which build a resolved artifact must come from
+#[derive(Debug, Clone, Copy)]
+pub enum Freshness {
+    /// Written by the build that started at this time
+    BuiltSince(SystemTime),
+    /// At least as new as every source file, i.e. the code has not changed
+    /// since the artifact was built
+    NewerThanSources,
+}
+
+/// Directory of a user's project
+pub fn project_dir(user_id: &UserId, project_id: &ProjectId) -> PathBuf {
+    user_dir(user_id).join(project_id.as_str())
+}
+
+/// Directory holding all of a user's projects
+pub fn user_dir(user_id: &UserId) -> PathBuf {
+    PathBuf::from("projects").join(user_id.as_str())
+}
+
+/// The artifact cargo-near wrote to `target/near` for the project's crate.
+/// Leftovers from a renamed crate are never picked, and an artifact older
+/// than `freshness` allows is reported as stale rather than returned.
+pub fn resolve_artifact(
+    project_path: &Path,
+    kind: ArtifactKind,
+    freshness: Freshness,
+) -> Result<PathBuf, ServiceError> {
+    let near_dir = project_path.join("target").join("near");
+    let project = project_path.to_string_lossy().to_string();
+    let candidates: Vec<String> = fs::read_dir(&near_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_file())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| kind.matches(name))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let path = match crate_name(project_path) {
+        Some(crate_name) => {
+            let expected = kind.file_name(&crate_name);
+            if !candidates.contains(&expected) {
+                if candidates.is_empty() {
+                    return Err(ServiceError::ArtifactNotFound {
+                        project,
+                        artifact: kind.as_str().to_string(),
+                    });
+                }
+                return Err(ServiceError::ArtifactMismatch {
+                    artifact: kind.as_str().to_string(),
+                    reason: format!(
+                        "expected {} for crate '{}' but found only {}; these are left over from an earlier crate name",
+                        expected,
+                        crate_name,
+                        candidates.join(", ")
+                    ),
+                });
+            }
+            near_dir.join(expected)
+        }
+        None => match candidates.as_slice() {
+            [] => {
+                return Err(ServiceError::ArtifactNotFound {
+                    project,
+                    artifact: kind.as_str().to_string(),
+                })
+            }
+            [only] => near_dir.join(only),
+            _ => {
+                return Err(ServiceError::ArtifactMismatch {
+                    artifact: kind.as_str().to_string(),
+                    reason: format!(
+                        "Cargo.toml has no crate name and several candidates exist: {}",
+                        candidates.join(", ")
+                    ),
+                })
+            }
+        },
+    };
+
+    check_freshness(project_path, &path, kind, freshness)?;
+    Ok(path)
+}
+
+fn check_freshness(
+    project_path: &Path,
+    path: &Path,
+    kind: ArtifactKind,
+    freshness: Freshness,
+) -> Result<(), ServiceError> {
+    let modified = modified(path).unwrap_or(SystemTime::UNIX_EPOCH);
+    let stale = |reason: &str| ServiceError::ArtifactMismatch {
+        artifact: kind.as_str().to_string(),
+        reason: format!(
+            "{} {}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            reason
+        ),
+    };
+
+    match freshness {
+        Freshness::BuiltSince(started) => {
+            if modified + MTIME_SLACK < started {
+                return Err(stale("was not written by this build"));
+            }
+        }
+        Freshness::NewerThanSources => {
+            if newest_source(project_path, Path::new("")).is_some_and(|source| source > modified) {
+                return Err(stale("is older than the project sources; compile the project again"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Name of the project's library crate: `[lib] name`, or the package name
+/// with dashes replaced as cargo does
+fn crate_name(project_path: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(project_path.join("Cargo.toml")).ok()?;
+    let manifest = manifest.parse::<toml_edit::DocumentMut>().ok()?;
+    let name = manifest
+        .get("lib")
+        .and_then(|lib| lib.get("name"))
+        .or_else(|| manifest.get("package")?.get("name"))?
+        .as_str()?;
+    Some(name.replace('-', "_"))
+}
+
+/// Latest modification time of any file outside `target` and `.git`
+pub fn newest_source(root: &Path, relative: &Path) -> Option<SystemTime> {
+    let entries = fs::read_dir(root.join(relative)).ok()?;
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            !(relative.as_os_str().is_empty() && (entry.file_name() == "target" || entry.file_name() == ".git"))
+        })
+        .filter_map(|entry| {
+            let file_type = entry.file_type().ok()?;
+            if file_type.is_dir() {
+                newest_source(root, &relative.join(entry.file_name()))
+            } else if file_type.is_file() {
+                modified(&entry.path())
+            } else {
+                None
+            }
+        })
+        .max()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_modified(path: &Path, time: SystemTime) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_resolve_artifact_by_crate_name() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"hello-near\"\n").unwrap();
+        let near_dir = dir.path().join("target/near");
+        fs::create_dir_all(&near_dir).unwrap();
+
+        assert!(matches!(
+            resolve_artifact(dir.path(), ArtifactKind::Wasm, Freshness::NewerThanSources),
+            Err(ServiceError::ArtifactNotFound { .. })
+        ));
+
+        // Left over from before the crate was renamed
+        fs::write(near_dir.join("contract.wasm"), "old").unwrap();
+        assert!(matches!(
+            resolve_artifact(dir.path(), ArtifactKind::Wasm, Freshness::NewerThanSources),
+            Err(ServiceError::ArtifactMismatch { .. })
+        ));
+
+        fs::write(near_dir.join("hello_near.wasm"), "new").unwrap();
+        fs::write(near_dir.join("hello_near_abi.json"), "{}").unwrap();
+        assert_eq!(
+            resolve_artifact(dir.path(), ArtifactKind::Wasm, Freshness::NewerThanSources).unwrap(),
+            near_dir.join("hello_near.wasm")
+        );
+        assert_eq!(
+            resolve_artifact(dir.path(), ArtifactKind::Abi, Freshness::NewerThanSources).unwrap(),
+            near_dir.join("hello_near_abi.json")
+        );
+    }
+
+    #[test]
+    fn test_resolve_artifact_rejects_stale_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"counter\"\n").unwrap();
+        let near_dir = dir.path().join("target/near");
+        fs::create_dir_all(&near_dir).unwrap();
+        let wasm = near_dir.join("counter.wasm");
+        fs::write(&wasm, "wasm").unwrap();
+
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        set_modified(&wasm, an_hour_ago);
+
+        // Sources edited after the build
+        assert!(matches!(
+            resolve_artifact(dir.path(), ArtifactKind::Wasm, Freshness::NewerThanSources),
+            Err(ServiceError::ArtifactMismatch { .. })
+        ));
+        assert!(matches!(
+            resolve_artifact(dir.path(), ArtifactKind::Wasm, Freshness::BuiltSince(SystemTime::now())),
+            Err(ServiceError::ArtifactMismatch { .. })
+        ));
+        assert!(resolve_artifact(
+            dir.path(),
+            ArtifactKind::Wasm,
+            Freshness::BuiltSince(an_hour_ago - Duration::from_secs(60))
+        )
+        .is_ok());
+    }
+}
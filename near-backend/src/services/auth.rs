@@ -0,0 +1,237 @@
+use actix_web::dev::Payload;
+use actix_web::error::ErrorUnauthorized;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use log::warn;
+use serde::Deserialize;
+use std::env;
+use std::future::{ready, Ready};
+
+use crate::errors::ServiceError;
+use crate::models::UserId;
+
+/// Audience Supabase sets on the access tokens of signed-in users
+const DEFAULT_AUDIENCE: &str = "authenticated";
+
+/// Verifies the bearer tokens API callers send: HS256 JWTs signed with
+/// `JWT_SECRET`, such as Supabase session access tokens, whose `sub` is the
+/// caller's user id.
+#[derive(Clone)]
+pub struct Authenticator {
+    mode: AuthMode,
+}
+
+#[derive(Clone)]
+enum AuthMode {
+    Jwt { key: DecodingKey, validation: Box<Validation> },
+    /// Callers name their own user, for local development only
+    Disabled,
+    /// No secret is configured, so every protected request is refused
+    Unconfigured,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+}
+
+/// The user a request was authenticated as, set by the auth middleware
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    /// `None` only while authentication is disabled
+    user_id: Option<UserId>,
+}
+
+impl Authenticator {
+    /// Read `JWT_SECRET`, `JWT_AUDIENCE` (default `authenticated`, empty to
+    /// skip the check), `JWT_ISSUER` and `AUTH_DISABLED`
+    pub fn from_env() -> Self {
+        let secret = env::var("JWT_SECRET").ok().filter(|secret| !secret.is_empty());
+        let disabled = env::var("AUTH_DISABLED").is_ok_and(|value| value == "true");
+
+        match secret {
+            _ if disabled => {
+                warn!("AUTH_DISABLED is set: API callers are trusted to name their own user");
+                Self { mode: AuthMode::Disabled }
+            }
+            Some(secret) => Self::hs256(
+                secret.as_bytes(),
+                &env::var("JWT_AUDIENCE").unwrap_or_else(|_| DEFAULT_AUDIENCE.to_string()),
+                env::var("JWT_ISSUER").ok().filter(|issuer| !issuer.is_empty()).as_deref(),
+            ),
+            None => {
+                warn!("JWT_SECRET is not set: compile, deploy and project requests will be refused");
+                Self { mode: AuthMode::Unconfigured }
+            }
+        }
+    }
+
+    fn hs256(secret: &[u8], audience: &str, issuer: Option<&str>) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        if audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&[audience]);
+        }
+        if let Some(issuer) = issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        Self {
+            mode: AuthMode::Jwt {
+                key: DecodingKey::from_secret(secret),
+                validation: Box::new(validation),
+            },
+        }
+    }
+
+    /// Check the value of an `Authorization: Bearer <token>` header
+    pub fn authenticate(&self, authorization: Option<&str>) -> Result<AuthenticatedUser, ServiceError> {
+        let unauthenticated = |reason: &str| ServiceError::Unauthenticated {
+            reason: reason.to_string(),
+        };
+
+        let (key, validation) = match &self.mode {
+            AuthMode::Jwt { key, validation } => (key, validation),
+            AuthMode::Disabled => return Ok(AuthenticatedUser { user_id: None }),
+            AuthMode::Unconfigured => {
+                return Err(unauthenticated("authentication is not configured on this server"))
+            }
+        };
+
+        let token = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| unauthenticated("send an Authorization: Bearer <access token> header"))?;
+        let claims = decode::<Claims>(token, key, validation)
+            .map_err(|e| unauthenticated(&format!("invalid access token ({})", e)))?
+            .claims;
+        let user_id = UserId::try_from(claims.sub)
+            .map_err(|_| unauthenticated("the access token's subject is not a valid user id"))?;
+
+        Ok(AuthenticatedUser {
+            user_id: Some(user_id),
+        })
+    }
+}
+
+impl AuthenticatedUser {
+    /// The user a request acts for. With a token that is the token's user,
+    /// and any user id the request names must match it.
+    pub fn resolve(&self, claimed: Option<&UserId>) -> Result<UserId, ServiceError> {
+        match (&self.user_id, claimed) {
+            (Some(user_id), Some(claimed)) if user_id != claimed => Err(ServiceError::UserMismatch {
+                user_id: claimed.to_string(),
+            }),
+            (Some(user_id), _) | (None, Some(user_id)) => Ok(user_id.clone()),
+            (None, None) => Err(ServiceError::Unauthenticated {
+                reason: "user_id is required while authentication is disabled".to_string(),
+            }),
+        }
+    }
+
+    /// The token's user; `None` while authentication is disabled
+    pub fn user_id(&self) -> Option<&UserId> {
+        self.user_id.as_ref()
+    }
+
+    /// Whether a resource belonging to `user_id`, such as a compile job, may be accessed
+    pub fn owns(&self, user_id: &str) -> bool {
+        self.user_id.as_ref().is_none_or(|own| own.as_str() == user_id)
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        // Only missing when a route is registered outside the auth middleware
+        ready(
+            req.extensions()
+                .get::<AuthenticatedUser>()
+                .cloned()
+                .ok_or_else(|| ErrorUnauthorized("request was not authenticated")),
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    pub(crate) const SECRET: &str = "test-secret";
+
+    /// A Supabase-style access token for `sub`, expiring `expires_in` seconds from now
+    pub(crate) fn token(secret: &str, sub: &str, audience: &str, expires_in: i64) -> String {
+        let claims = serde_json::json!({
+            "sub": sub,
+            "aud": audience,
+            "role": "authenticated",
+            "exp": chrono::Utc::now().timestamp() + expires_in,
+        });
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+    }
+
+    pub(crate) fn authenticator() -> Authenticator {
+        Authenticator::hs256(SECRET.as_bytes(), DEFAULT_AUDIENCE, None)
+    }
+
+    fn user(id: &str) -> UserId {
+        UserId::try_from(id.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_tokens_are_verified() {
+        let auth = authenticator();
+        let bearer = |token: String| format!("Bearer {}", token);
+
+        let alice = auth
+            .authenticate(Some(&bearer(token(SECRET, "alice", DEFAULT_AUDIENCE, 3600))))
+            .unwrap();
+        assert_eq!(alice.resolve(None).unwrap(), user("alice"));
+
+        for header in [
+            None,
+            Some("alice".to_string()),
+            Some(bearer(token("other-secret", "alice", DEFAULT_AUDIENCE, 3600))),
+            Some(bearer(token(SECRET, "alice", DEFAULT_AUDIENCE, -3600))),
+            Some(bearer(token(SECRET, "alice", "anon", 3600))),
+            Some(bearer(token(SECRET, "../alice", DEFAULT_AUDIENCE, 3600))),
+        ] {
+            assert!(
+                matches!(
+                    auth.authenticate(header.as_deref()),
+                    Err(ServiceError::Unauthenticated { .. })
+                ),
+                "{:?} should be rejected",
+                header
+            );
+        }
+
+        let unconfigured = Authenticator {
+            mode: AuthMode::Unconfigured,
+        };
+        assert!(unconfigured.authenticate(Some(&bearer(token(SECRET, "alice", DEFAULT_AUDIENCE, 3600)))).is_err());
+    }
+
+    #[test]
+    fn test_resolve_prefers_the_token_user() {
+        let alice = AuthenticatedUser {
+            user_id: Some(user("alice")),
+        };
+        assert_eq!(alice.resolve(Some(&user("alice"))).unwrap(), user("alice"));
+        assert!(matches!(
+            alice.resolve(Some(&user("bob"))),
+            Err(ServiceError::UserMismatch { .. })
+        ));
+        assert!(alice.owns("alice") && !alice.owns("bob"));
+
+        let anyone = AuthenticatedUser { user_id: None };
+        assert_eq!(anyone.resolve(Some(&user("bob"))).unwrap(), user("bob"));
+        assert!(anyone.resolve(None).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+use crate::models::{BuildEnvironment, BuildMode, CompileEvent};
+use crate::services::process::run_command;
+use crate::services::sandbox::{cargo_home, restrict_env, Sandbox};
+use crate::services::toolchains::locked_sdk_version;
+
+/// Path the project and cargo home are remapped to in local reproducible
+/// builds, matching the layout of cargo-near's build image
+const REMAPPED_PROJECT_PATH: &str = "/home/near/code";
+const REMAPPED_CARGO_HOME: &str = "/home/near/.cargo";
+
+/// Reported for reproducible requests built by the local stand-in, whose
+/// output cannot be verified like cargo-near's Docker builds
+const PINNED_LOCAL_MODE: &str = "pinned-local";
+
+/// Wall-clock limit for `rustc -V` and `cargo near --version`
+const VERSION_QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How `reproducible-wasm` requests are built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReproducibleBackend {
+    /// Stand-in without Docker: a pinned toolchain, `--locked`, no
+    /// incremental state and host paths remapped out of the binary. Reported
+    /// as `pinned-local`, since it is not a verifiable reproducible build.
+    Local { toolchain: Option<String> },
+    /// `cargo near build reproducible-wasm` in the image pinned by the
+    /// project's `[package.metadata.near.reproducible_build]`, which must
+    /// already be present locally
+    Docker,
+}
+
+/// The `cargo near build` invocation for a requested build mode
+#[derive(Debug, Clone)]
+pub struct BuildCommand {
+    mode: BuildMode,
+    backend: Option<ReproducibleBackend>,
+}
+
+impl BuildCommand {
+    /// Read `REPRODUCIBLE_BUILD_BACKEND` (`local` or `docker`) and
+    /// `REPRODUCIBLE_TOOLCHAIN` for reproducible builds
+    pub fn from_env(mode: BuildMode) -> Self {
+        let backend = match mode {
+            BuildMode::NonReproducible => None,
+            BuildMode::Reproducible => Some(match env::var("REPRODUCIBLE_BUILD_BACKEND").as_deref() {
+                Ok("docker") => ReproducibleBackend::Docker,
+                Ok("local") | Err(_) => ReproducibleBackend::Local {
+                    toolchain: env::var("REPRODUCIBLE_TOOLCHAIN").ok().filter(|value| !value.is_empty()),
+                },
+                Ok(other) => {
+                    warn!("Unknown REPRODUCIBLE_BUILD_BACKEND '{}', using local", other);
+                    ReproducibleBackend::Local { toolchain: None }
+                }
+            }),
+        };
+
+        Self { mode, backend }
+    }
+
+    /// Distinguishes results of different build setups in the compile cache
+    pub fn cache_tag(&self) -> String {
+        match &self.backend {
+            None => self.mode.as_str().to_string(),
+            Some(ReproducibleBackend::Local { toolchain }) => format!(
+                "{}:local:{}",
+                self.mode.as_str(),
+                toolchain.as_deref().unwrap_or("default")
+            ),
+            Some(ReproducibleBackend::Docker) => format!("{}:docker", self.mode.as_str()),
+        }
+    }
+
+    /// Arguments to `cargo`
+    pub fn args(&self) -> Vec<&'static str> {
+        match &self.backend {
+            None => vec!["near", "build", "non-reproducible-wasm"],
+            Some(ReproducibleBackend::Local { .. }) => {
+                vec!["near", "build", "non-reproducible-wasm", "--locked"]
+            }
+            Some(ReproducibleBackend::Docker) => vec!["near", "build", "reproducible-wasm"],
+        }
+    }
+
+    /// Environment that pins the local reproducible build
+    pub fn envs(&self, project_path: &Path) -> Result<Vec<(String, String)>> {
+        let Some(ReproducibleBackend::Local { toolchain }) = &self.backend else {
+            return Ok(Vec::new());
+        };
+
+        let project = fs::canonicalize(project_path).context("Failed to resolve project directory")?;
+        let mut rustflags = format!(
+            "--remap-path-prefix={}={}",
+            project.display(),
+            REMAPPED_PROJECT_PATH
+        );
+        if let Some(home) = cargo_home() {
+            rustflags.push_str(&format!(" --remap-path-prefix={}={}", home.display(), REMAPPED_CARGO_HOME));
+        }
+
+        let mut envs = vec![
+            ("RUSTFLAGS".to_string(), rustflags),
+            ("CARGO_INCREMENTAL".to_string(), "0".to_string()),
+            ("SOURCE_DATE_EPOCH".to_string(), "0".to_string()),
+        ];
+        if let Some(toolchain) = toolchain {
+            envs.push(("RUSTUP_TOOLCHAIN".to_string(), toolchain.clone()));
+        }
+        Ok(envs)
+    }
+
+    /// The build command for a project. Docker builds run outside the
+    /// sandbox, under its resource limits: the build image is their
+    /// isolation and they need the daemon.
+    pub fn command(&self, sandbox: &Sandbox, project_path: &Path) -> Result<Command> {
+        let mut command = match self.backend {
+            Some(ReproducibleBackend::Docker) => sandbox.host_command("cargo", project_path, &self.args()),
+            _ => sandbox.cargo_command(project_path, &self.args())?,
+        };
+        command.envs(self.envs(project_path)?);
+        Ok(command)
+    }
+
+    /// Docker builds require the pinned image locally and a clean git tree;
+    /// the project's current state is committed so cargo-near can record it
+    pub async fn prepare(
+        &self,
+        sandbox: &Sandbox,
+        project_path: &Path,
+        deadline: Instant,
+        on_event: &dyn Fn(CompileEvent),
+    ) -> Result<()> {
+        if self.backend != Some(ReproducibleBackend::Docker) {
+            return Ok(());
+        }
+
+        let (image, digest) = pinned_image(project_path);
+        let image = image.context(
+            "Reproducible builds need [package.metadata.near.reproducible_build] with an image in Cargo.toml",
+        )?;
+        let reference = match &digest {
+            Some(digest) => format!("{}@{}", image_name(&image), digest),
+            None => image.clone(),
+        };
+        // The reference comes from the user's manifest, so it must not be read as an option
+        let inspect = sandbox.host_command("docker", project_path, &["image", "inspect", "--", &reference]);
+        let output = run_command(inspect, deadline, &|_| {}).await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Reproducible build image {} is not available locally",
+                reference
+            ));
+        }
+
+        on_event(CompileEvent::Progress {
+            message: "Committing project state for reproducible build".to_string(),
+        });
+        let steps: [&[&str]; 3] = [
+            &["init", "-q"],
+            &["add", "-A"],
+            &[
+                "-c",
+                "user.name=NEAR Playground",
+                "-c",
+                "user.email=builds@nearplay.local",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "Reproducible build",
+            ],
+        ];
+        for args in steps {
+            let git = sandbox.command("git", project_path, args)?;
+            let output = run_command(git, deadline, &|_| {}).await?;
+            if !output.status.success() {
+                return Err(anyhow::anyhow!(
+                    "Failed to prepare git tree for reproducible build: {}",
+                    output.stderr
+                ));
+            }
+        }
+
+        debug!("Committed {:?} for reproducible build", project_path);
+        Ok(())
+    }
+
+    /// Describe the toolchain and tooling a build used
+    pub async fn environment(&self, project_path: &Path) -> BuildEnvironment {
+        let pinned = match &self.backend {
+            Some(ReproducibleBackend::Local { toolchain }) => toolchain.clone(),
+            _ => None,
+        };
+        let with_toolchain = |program: &str| {
+            let mut command = Command::new(program);
+            restrict_env(&mut command);
+            command.current_dir(project_path);
+            if let Some(toolchain) = &pinned {
+                command.env("RUSTUP_TOOLCHAIN", toolchain);
+            }
+            command
+        };
+
+        let mut rustc = with_toolchain("rustc");
+        rustc.arg("-V");
+        let mut cargo_near = with_toolchain("cargo");
+        cargo_near.args(["near", "--version"]);
+
+        let (image, image_digest) = match self.backend {
+            Some(ReproducibleBackend::Docker) => pinned_image(project_path),
+            _ => (None, None),
+        };
+
+        BuildEnvironment {
+            mode: match self.backend {
+                Some(ReproducibleBackend::Local { .. }) => PINNED_LOCAL_MODE.to_string(),
+                _ => self.mode.as_str().to_string(),
+            },
+            backend: self.backend.as_ref().map(|backend| match backend {
+                ReproducibleBackend::Local { .. } => "local".to_string(),
+                ReproducibleBackend::Docker => "docker".to_string(),
+            }),
+            toolchain: first_line(rustc).await.unwrap_or_else(|| "unknown".to_string()),
+            near_sdk: locked_sdk_version(project_path),
+            cargo_near: first_line(cargo_near).await,
+            image,
+            image_digest,
+            locked: self.backend.is_some(),
+        }
+    }
+}
+
+/// `image` and `image_digest` from the project's reproducible build metadata
+fn pinned_image(project_path: &Path) -> (Option<String>, Option<String>) {
+    let manifest = fs::read_to_string(project_path.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| manifest.parse::<toml_edit::DocumentMut>().ok());
+    let field = |name: &str| {
+        let manifest = manifest.as_ref()?;
+        manifest
+            .get("package")?
+            .get("metadata")?
+            .get("near")?
+            .get("reproducible_build")?
+            .get(name)?
+            .as_str()
+            .map(str::to_string)
+    };
+    (field("image"), field("image_digest"))
+}
+
+/// Image reference without its tag; a registry port is not a tag
+fn image_name(image: &str) -> &str {
+    match image.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => name,
+        _ => image,
+    }
+}
+
+async fn first_line(command: Command) -> Option<String> {
+    let description = format!("{:?}", command.as_std());
+    match run_command(command, Instant::now() + VERSION_QUERY_TIMEOUT, &|_| {}).await {
+        Ok(output) if output.status.success() => output.stdout.lines().next().map(|line| line.trim().to_string()),
+        Ok(output) => {
+            info!("{} failed: {}", description, output.stderr);
+            None
+        }
+        Err(e) => {
+            info!("Failed to run {}: {:#}", description, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn test_local_reproducible_build_is_pinned() {
+        let dir = tempfile::tempdir().unwrap();
+        let build = BuildCommand {
+            mode: BuildMode::Reproducible,
+            backend: Some(ReproducibleBackend::Local {
+                toolchain: Some("1.86.0".to_string()),
+            }),
+        };
+
+        assert!(build.args().contains(&"--locked"));
+        assert_eq!(build.cache_tag(), "reproducible-wasm:local:1.86.0");
+        let unpinned = BuildCommand {
+            mode: BuildMode::Reproducible,
+            backend: Some(ReproducibleBackend::Local { toolchain: None }),
+        };
+        assert_eq!(unpinned.environment(dir.path()).await.mode, PINNED_LOCAL_MODE);
+
+        let envs = build.envs(dir.path()).unwrap();
+        let rustflags = &envs.iter().find(|(name, _)| name == "RUSTFLAGS").unwrap().1;
+        let project = fs::canonicalize(dir.path()).unwrap();
+        assert!(rustflags.contains(&format!("{}={}", project.display(), REMAPPED_PROJECT_PATH)));
+        assert!(envs.contains(&("RUSTUP_TOOLCHAIN".to_string(), "1.86.0".to_string())));
+
+        let default = BuildCommand::from_env(BuildMode::NonReproducible);
+        assert_eq!(default.args(), vec!["near", "build", "non-reproducible-wasm"]);
+        assert!(default.envs(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pinned_image_from_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            r#"[package]
+name = "contract"
+
+[package.metadata.near.reproducible_build]
+image = "sourcescan/cargo-near:0.13.4-rust-1.85.0"
+image_digest = "sha256:a9d8bee7b134856cc8baa142494a177f2ba9ecfededfcdd38f634e14cca8aae2"
+"#,
+        )
+        .unwrap();
+
+        let (image, digest) = pinned_image(dir.path());
+        assert_eq!(image.as_deref(), Some("sourcescan/cargo-near:0.13.4-rust-1.85.0"));
+        assert!(digest.unwrap().starts_with("sha256:"));
+        assert_eq!(image_name("sourcescan/cargo-near:0.13.4-rust-1.85.0"), "sourcescan/cargo-near");
+        assert_eq!(image_name("localhost:5000/cargo-near"), "localhost:5000/cargo-near");
+    }
+}
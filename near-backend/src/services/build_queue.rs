@@ -0,0 +1,248 @@
+use log::{debug, warn};
+use std::collections::VecDeque;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::errors::ServiceError;
+
+const DEFAULT_MAX_CONCURRENT_BUILDS: usize = 2;
+const DEFAULT_MAX_QUEUED_BUILDS: usize = 20;
+
+/// Assumed build duration until a real one has been measured
+const INITIAL_BUILD_ESTIMATE: Duration = Duration::from_secs(30);
+
+struct QueueState {
+    max_concurrent: usize,
+    max_queued: usize,
+    running: usize,
+    next_ticket: u64,
+    waiting: VecDeque<Waiter>,
+    average_build: Duration,
+}
+
+/// A ticket in line; `sender` is set once its holder is ready to build
+struct Waiter {
+    id: u64,
+    sender: Option<oneshot::Sender<()>>,
+}
+
+/// FIFO limiter for `cargo` processes: at most `max_concurrent` builds run
+/// while up to `max_queued` more wait their turn. A ticket keeps its place
+/// in line while its holder waits for other things, such as the project
+/// lock, but only takes a slot once it asks for one.
+#[derive(Clone)]
+pub struct BuildQueue {
+    state: Arc<Mutex<QueueState>>,
+}
+
+/// A place in the queue; waiting on it yields a permit to build
+pub struct BuildTicket {
+    queue: BuildQueue,
+    id: u64,
+    /// Set while waiting for a slot; owned by the ticket so it outlives the
+    /// ticket's `Drop`, which must see whether a slot was handed over
+    receiver: Option<oneshot::Receiver<()>>,
+    /// Set once the slot has been handed over to a `BuildPermit`
+    transferred: bool,
+}
+
+/// Held for the duration of a build; frees the slot for the next ticket on drop
+pub struct BuildPermit {
+    queue: BuildQueue,
+    started: Instant,
+}
+
+impl BuildQueue {
+    pub fn new(max_concurrent: usize, max_queued: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(QueueState {
+                max_concurrent: max_concurrent.max(1),
+                max_queued,
+                running: 0,
+                next_ticket: 0,
+                waiting: VecDeque::new(),
+                average_build: INITIAL_BUILD_ESTIMATE,
+            })),
+        }
+    }
+
+    /// Limits from `MAX_CONCURRENT_BUILDS` and `MAX_QUEUED_BUILDS`
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: usize| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        Self::new(
+            read("MAX_CONCURRENT_BUILDS", DEFAULT_MAX_CONCURRENT_BUILDS),
+            read("MAX_QUEUED_BUILDS", DEFAULT_MAX_QUEUED_BUILDS),
+        )
+    }
+
+    /// Take a place in line, or refuse when the queue is already full
+    pub fn enqueue(&self) -> Result<BuildTicket, ServiceError> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_ticket;
+        state.next_ticket += 1;
+
+        // Free slots take tickets straight from the line
+        let free = state.max_concurrent.saturating_sub(state.running);
+        if state.waiting.len() >= state.max_queued + free {
+            let queued = state.waiting.len().saturating_sub(free);
+            let retry_after_secs = estimate_wait(&state, queued);
+            warn!("Build queue full ({} waiting)", queued);
+            return Err(ServiceError::QueueFull {
+                queued,
+                retry_after_secs,
+            });
+        }
+
+        state.waiting.push_back(Waiter { id, sender: None });
+        debug!("Build ticket {} queued at position {}", id, state.waiting.len());
+
+        Ok(BuildTicket {
+            queue: self.clone(),
+            id,
+            receiver: None,
+            transferred: false,
+        })
+    }
+
+    /// 1-based position of a waiting ticket, `None` once it is running
+    pub fn position(&self, ticket_id: u64) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        state
+            .waiting
+            .iter()
+            .position(|waiter| waiter.id == ticket_id)
+            .map(|index| index + 1)
+    }
+
+    /// Hand a freed slot to the next live waiter
+    fn release(&self, build_time: Option<Duration>) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(build_time) = build_time {
+            // Exponential moving average keeps retry hints close to recent builds
+            state.average_build = (state.average_build * 3 + build_time) / 4;
+        }
+        state.running -= 1;
+
+        // The first ticket whose holder is ready to build, in line order
+        while let Some(index) = state.waiting.iter().position(|waiter| waiter.sender.is_some()) {
+            let Some(Waiter { id, sender: Some(sender) }) = state.waiting.remove(index) else {
+                continue;
+            };
+            if sender.send(()).is_ok() {
+                state.running += 1;
+                debug!("Build ticket {} admitted", id);
+                break;
+            }
+        }
+    }
+}
+
+impl BuildTicket {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Wait until a build slot is free. Only now does the ticket compete for
+    /// one, ahead of later tickets but behind earlier ones that are also waiting.
+    pub async fn acquire(mut self) -> BuildPermit {
+        self.receiver = {
+            let mut state = self.queue.state.lock().unwrap();
+            let ready_ahead = state
+                .waiting
+                .iter()
+                .take_while(|waiter| waiter.id != self.id)
+                .any(|waiter| waiter.sender.is_some());
+            if state.running < state.max_concurrent && !ready_ahead {
+                state.waiting.retain(|waiter| waiter.id != self.id);
+                state.running += 1;
+                None
+            } else {
+                let (sender, receiver) = oneshot::channel();
+                if let Some(waiter) = state.waiting.iter_mut().find(|waiter| waiter.id == self.id) {
+                    waiter.sender = Some(sender);
+                }
+                Some(receiver)
+            }
+        };
+        if let Some(receiver) = self.receiver.as_mut() {
+            // Our sender only goes away after the queue handed us a slot
+            let _ = receiver.await;
+        }
+
+        self.transferred = true;
+        BuildPermit {
+            queue: self.queue.clone(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for BuildTicket {
+    fn drop(&mut self) {
+        if self.transferred {
+            return;
+        }
+        let mut state = self.queue.state.lock().unwrap();
+        let before = state.waiting.len();
+        state.waiting.retain(|waiter| waiter.id != self.id);
+        if state.waiting.len() < before {
+            return;
+        }
+        drop(state);
+        // Admitted while nobody was waiting on us any more
+        self.queue.release(None);
+    }
+}
+
+impl Drop for BuildPermit {
+    fn drop(&mut self) {
+        self.queue.release(Some(self.started.elapsed()));
+    }
+}
+
+fn estimate_wait(state: &QueueState, queued: usize) -> u64 {
+    let rounds = queued / state.max_concurrent + 1;
+    (state.average_build * rounds as u32).as_secs().max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+
+    #[actix_rt::test]
+    async fn test_queue_is_fifo_and_bounded() {
+        let queue = BuildQueue::new(1, 2);
+
+        let first = queue.enqueue().unwrap().acquire().await;
+        let second = queue.enqueue().unwrap();
+        let third = queue.enqueue().unwrap();
+        assert_eq!(queue.position(second.id()), Some(1));
+        assert_eq!(queue.position(third.id()), Some(2));
+
+        assert!(matches!(
+            queue.enqueue(),
+            Err(ServiceError::QueueFull { queued: 2, .. })
+        ));
+
+        // A ticket abandoned while waiting gives up its place
+        drop(second);
+        assert_eq!(queue.position(third.id()), Some(1));
+
+        drop(first);
+        let third_id = third.id();
+        let third = third.acquire().await;
+        assert_eq!(queue.position(third_id), None);
+
+        drop(third);
+        let next = queue.enqueue().unwrap();
+        assert!(next.acquire().now_or_never().is_some());
+    }
+}
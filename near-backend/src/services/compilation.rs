@@ -2,45 +2,194 @@ use anyhow::{Context, Result};
 use log::{debug, error, info, warn};
 use serde_json;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
-use std::time::Instant;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::env;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::process::Command;
 
-use crate::models::{CompileDetails, CompileResponse};
+use crate::models::{
+    AbiWarning, BuildMode, CompileDetails, CompileEvent, CompileRequest, CompileResponse, Diagnostic,
+    OptimizationLevel, OptimizationReport, ProjectTemplate, UserId, WasmReport,
+};
+use crate::services::abi_check::check_abi_exports;
+use crate::services::artifacts::{project_dir, resolve_artifact, ArtifactKind, Freshness};
+use crate::services::build_mode::BuildCommand;
+use crate::services::compile_cache::{input_hash, CompileCache};
+use crate::services::dependencies::{apply_dependency_changes, DependencyAllowlist};
+use crate::services::dependency_cache::{toolchain_version, DependencyCache};
+use crate::services::diagnostics::{parse_cargo_messages, CHECK_ARGS};
+use crate::services::process::{run_command, BuildOutput};
+use crate::services::project_files::{apply_file_changes, remove_build_config};
+use crate::services::projects::record_build;
+use crate::services::sandbox::{restrict_env, Sandbox};
+use crate::services::storage_cost::StoragePricing;
+use crate::services::templates::TemplateRegistry;
+use crate::services::toolchains::ToolchainCatalog;
+use crate::services::wasm_optimize::optimize_wasm;
+use crate::services::wasm_report::analyze_wasm;
+use crate::utils::code_hash;
 
+/// Wall-clock budget for all cargo invocations of one compile request
+const DEFAULT_BUILD_TIMEOUT_SECS: u64 = 300;
+
+/// Build timeout from `BUILD_TIMEOUT_SECS`
+pub fn build_timeout() -> Duration {
+    let secs = env::var("BUILD_TIMEOUT_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_BUILD_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Compile the project, reporting cargo output line by line through `on_event`
 pub async fn compile_contract(
-    code: &str,
-    user_id: &str,
-    project_id: &str,
+    user_id: &UserId,
+    request: &CompileRequest,
+    on_event: &dyn Fn(CompileEvent),
 ) -> Result<CompileResponse> {
     let start_time = Instant::now();
+    let started_at = SystemTime::now();
+    let deadline = start_time + build_timeout();
+    let project_id = request.project_id.as_str();
     
     // Use persistent project directory structure
-    let base_project_path = PathBuf::from("base_project");
-    let user_projects_dir = PathBuf::from("projects").join(user_id);
-    let project_path = user_projects_dir.join(project_id);
+    let project_path = project_dir(user_id, &request.project_id);
+
+    // New projects start from the base project kept for their versions
+    let versions = ToolchainCatalog::from_env().select(
+        &project_path,
+        request.toolchain.as_deref(),
+        request.near_sdk_version.as_deref(),
+    )?;
+    let base_project_path = versions.base_project_path();
+    for change in &versions.changes {
+        on_event(CompileEvent::Progress {
+            message: format!(
+                "{} {} is no longer available, building with {}",
+                change.component,
+                change.previous,
+                change.current.as_deref().unwrap_or("the server default")
+            ),
+        });
+    }
     
     info!(
         "Starting compilation for project {} in persistent directory: {:?}",
         project_id, project_path
     );
 
+    // Templates only seed projects that do not exist yet
+    let template = match &request.template {
+        Some(id) if !project_path.exists() => Some(TemplateRegistry::from_env().get(id)?),
+        _ => None,
+    };
+
     // Ensure the project exists (copy from base if needed)
-    setup_user_project(&base_project_path, &project_path, code)?;
+    setup_user_project(
+        &base_project_path,
+        &project_path,
+        template.as_ref(),
+        &request.project_files(),
+        &request.deleted_files,
+    )?;
+    versions.apply(&project_path)?;
+
+    // Merge requested dependencies and drop anything not on the allowlist
+    let allowlist = DependencyAllowlist::from_env()?;
+    let dependencies: Vec<_> = template
+        .iter()
+        .flat_map(|template| template.dependencies.iter())
+        .chain(&request.dependencies)
+        .cloned()
+        .collect();
+    let refused_dependencies = apply_dependency_changes(
+        &project_path,
+        &base_project_path,
+        &dependencies,
+        &allowlist,
+    )?;
 
-    // Run cargo near build
-    let compile_result = run_cargo_near_build(&project_path)?;
+    // Identical inputs produce identical output, so reuse an earlier result
+    let compile_cache = CompileCache::from_env();
+    let toolchain = toolchain_version(&versions).await?;
+    let build = BuildCommand::from_env(request.build_mode);
+    // Rewriting a reproducible build would break verification against its source
+    let optimization = match request.build_mode {
+        BuildMode::Reproducible => OptimizationLevel::None,
+        BuildMode::NonReproducible => request.optimization,
+    };
+    if optimization != request.optimization {
+        on_event(CompileEvent::Progress {
+            message: "Skipping WASM optimization for a reproducible build".to_string(),
+        });
+    }
+    let cache_tag = format!("{}:opt-{}", build.cache_tag(), optimization.as_str());
+    let cache_key = input_hash(&project_path, &toolchain, &cache_tag)?;
+    match compile_cache.lookup(&cache_key, &project_path) {
+        Ok(Some(mut response)) => {
+            on_event(CompileEvent::Progress {
+                message: "Using cached build".to_string(),
+            });
+            response.details.compilation_time = start_time.elapsed().as_secs_f64();
+            response.details.project_path = project_path.to_string_lossy().to_string();
+            response.refused_dependencies = refused_dependencies;
+            response.version_changes = versions.changes;
+            if let Err(e) = record_build(&project_path, request.build_mode, &response) {
+                warn!("Failed to record build: {:#}", e);
+            }
+            return Ok(response);
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to read compile cache: {:#}", e),
+    }
+
+    // A project's first build starts from precompiled dependencies
+    if let Err(e) = DependencyCache::from_env().seed(&project_path, &toolchain) {
+        warn!("Failed to seed project from dependency cache: {:#}", e);
+    }
+
+    // User build scripts and proc macros only ever run inside the sandbox;
+    // when it has no network, dependencies are fetched beforehand
+    let sandbox = Sandbox::from_env();
+    if sandbox.is_offline() {
+        fetch_dependencies(&project_path, deadline, on_event).await?;
+    }
+
+    // Build straight away, then type-check for structured diagnostics so
+    // warnings are reported on successful builds too
+    build.prepare(&sandbox, &project_path, deadline, on_event).await?;
+    let compile_result = run_cargo_near_build(&build, &sandbox, &project_path, deadline, on_event).await?;
+    sandbox.check_exit(&compile_result.status, &compile_result.stderr)?;
+    on_event(CompileEvent::Progress {
+        message: "Collecting diagnostics".to_string(),
+    });
+    let diagnostics = collect_diagnostics(&sandbox, &project_path, deadline).await?;
     
+    sandbox.check_disk_usage(&project_path)?;
+
     let compilation_time = start_time.elapsed().as_secs_f64();
     
-    // Extract WASM file size and metadata
-    let (wasm_size, abi) = extract_compilation_artifacts(&project_path)?;
+    // Only a successful build leaves artifacts that belong to this request
+    let artifacts = if compile_result.status.success() {
+        extract_compilation_artifacts(&project_path, started_at, optimization)?
+    } else {
+        CompilationArtifacts::default()
+    };
+    let build_environment = build.environment(&project_path).await;
+    // A contract too large to fund still built, so only the estimate is dropped
+    let storage_estimate = artifacts.wasm_size.and_then(|size| {
+        StoragePricing::from_env()
+            .and_then(|pricing| pricing.estimate(size))
+            .map_err(|e| warn!("No storage estimate for {} bytes: {:#}", size, e))
+            .ok()
+    });
     
     let response = CompileResponse {
         success: compile_result.status.success(),
         exit_code: compile_result.status.code().unwrap_or(-1),
-        stdout: String::from_utf8_lossy(&compile_result.stdout).to_string(),
-        stderr: String::from_utf8_lossy(&compile_result.stderr).to_string(),
+        stdout: compile_result.stdout,
+        stderr: compile_result.stderr,
         details: CompileDetails {
             status: if compile_result.status.success() {
                 "success".to_string()
@@ -49,12 +198,34 @@ pub async fn compile_contract(
             },
             compilation_time,
             project_path: project_path.to_string_lossy().to_string(),
-            wasm_size,
-            optimized: true, // cargo-near builds optimized by default
+            wasm_size: artifacts.wasm_size,
+            code_hash: artifacts.code_hash,
+            optimized: artifacts
+                .optimization
+                .as_ref()
+                .is_some_and(|report| !report.passes.is_empty()),
         },
-        abi,
+        abi: artifacts.abi,
+        refused_dependencies,
+        diagnostics,
+        cache_hit: false,
+        build_environment: Some(build_environment),
+        wasm_report: artifacts.wasm_report,
+        abi_warnings: artifacts.abi_warnings,
+        storage_estimate,
+        optimization: artifacts.optimization,
+        version_changes: versions.changes,
     };
 
+    if response.success {
+        if let Err(e) = compile_cache.store(&cache_key, &project_path, &response) {
+            warn!("Failed to update compile cache: {:#}", e);
+        }
+    }
+    if let Err(e) = record_build(&project_path, request.build_mode, &response) {
+        warn!("Failed to record build: {:#}", e);
+    }
+
     info!(
         "Compilation completed for project {} in {:.2}s",
         project_id, compilation_time
@@ -63,7 +234,13 @@ pub async fn compile_contract(
     Ok(response)
 }
 
-fn setup_user_project(base_project_path: &PathBuf, project_path: &PathBuf, code: &str) -> Result<()> {
+fn setup_user_project(
+    base_project_path: &Path,
+    project_path: &Path,
+    template: Option<&ProjectTemplate>,
+    files: &BTreeMap<String, String>,
+    deleted_files: &[String],
+) -> Result<()> {
     // Check if user project already exists
     if !project_path.exists() {
         info!("Creating new project by copying base project template");
@@ -74,23 +251,32 @@ fn setup_user_project(base_project_path: &PathBuf, project_path: &PathBuf, code:
         }
         
         // Copy entire base project
-        copy_dir_all(base_project_path, project_path)
-            .context("Failed to copy base project template")?;
+        copy_project(base_project_path, project_path)?;
         
         debug!("Base project copied to: {:?}", project_path);
+
+        if let Some(template) = template {
+            apply_file_changes(project_path, &template.files, &[])?;
+            info!("Created project from template '{}'", template.id);
+        }
     }
     
-    // Always update lib.rs with user's code
-    let lib_rs_path = project_path.join("src").join("lib.rs");
-    fs::write(&lib_rs_path, code)
-        .context("Failed to write user contract code")?;
+    // Always sync the submitted file tree into the project
+    apply_file_changes(project_path, files, deleted_files)?;
+    remove_build_config(project_path)?;
     
-    debug!("Updated lib.rs with user code");
+    debug!("Synced {} file(s) into project", files.len());
     Ok(())
 }
 
+/// Copy a base project's sources, without its build output, to `project_path`
+pub fn copy_project(base_project_path: &Path, project_path: &Path) -> Result<()> {
+    copy_dir_all(base_project_path, project_path)
+        .with_context(|| format!("Failed to copy base project {:?}", base_project_path))
+}
+
 // Helper function to recursively copy directories
-fn copy_dir_all(src: &PathBuf, dst: &PathBuf) -> Result<()> {
+fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
     fs::create_dir_all(dst).context("Failed to create destination directory")?;
     
     for entry in fs::read_dir(src).context("Failed to read source directory")? {
@@ -115,15 +301,19 @@ fn copy_dir_all(src: &PathBuf, dst: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-fn run_cargo_near_build(project_path: &PathBuf) -> Result<std::process::Output> {
-    debug!("Running cargo near build non-reproducible-wasm in directory: {:?}", project_path);
+async fn run_cargo_near_build(
+    build: &BuildCommand,
+    sandbox: &Sandbox,
+    project_path: &Path,
+    deadline: Instant,
+    on_event: &dyn Fn(CompileEvent),
+) -> Result<BuildOutput> {
+    debug!("Running cargo {} in directory: {:?}", build.args().join(" "), project_path);
     
-    let output = Command::new("cargo")
-        .arg("near")
-        .arg("build")
-        .arg("non-reproducible-wasm")
-        .current_dir(project_path)
-        .output()
+    let command = build.command(sandbox, project_path)?;
+
+    let output = run_command(command, deadline, on_event)
+        .await
         .context("Failed to execute cargo near build")?;
 
     debug!(
@@ -134,57 +324,100 @@ fn run_cargo_near_build(project_path: &PathBuf) -> Result<std::process::Output>
     Ok(output)
 }
 
-fn extract_compilation_artifacts(project_path: &PathBuf) -> Result<(Option<u64>, Option<serde_json::Value>)> {
-    // Look for WASM file in target/near directory
-    let near_dir = project_path.join("target").join("near");
-
-    let wasm_size = if near_dir.exists() {
-        // Find .wasm files
-        let wasm_files: Vec<_> = fs::read_dir(&near_dir)
-            .context("Failed to read near target directory")?
-            .filter_map(|entry| {
-                let entry = entry.ok()?;
-                let path = entry.path();
-                if path.extension()? == "wasm" {
-                    Some(path)
-                } else {
-                    None
-                }
-            })
-            .collect();
+/// `cargo check` with JSON messages, for the diagnostics of a failed build.
+/// Its output repeats the build's, so none of it is streamed.
+/// Compiler messages from `cargo check`, warnings included
+async fn collect_diagnostics(sandbox: &Sandbox, project_path: &Path, deadline: Instant) -> Result<Vec<Diagnostic>> {
+    let check_result = run_cargo_check(sandbox, project_path, deadline).await?;
+    sandbox.check_exit(&check_result.status, &check_result.stderr)?;
+    Ok(parse_cargo_messages(&check_result.stdout))
+}
 
-        if let Some(wasm_file) = wasm_files.first() {
-            fs::metadata(wasm_file)
-                .map(|metadata| metadata.len())
-                .ok()
-        } else {
-            warn!("No WASM file found in target/near directory");
-            None
-        }
-    } else {
-        warn!("target/near directory not found");
+async fn run_cargo_check(sandbox: &Sandbox, project_path: &Path, deadline: Instant) -> Result<BuildOutput> {
+    debug!("Running cargo check for diagnostics in directory: {:?}", project_path);
+
+    let command = sandbox.cargo_command(project_path, &CHECK_ARGS)?;
+    let output = run_command(command, deadline, &|_| {})
+        .await
+        .context("Failed to execute cargo check")?;
+
+    debug!("cargo check completed with exit code: {:?}", output.status.code());
+
+    Ok(output)
+}
+
+/// Download and unpack dependencies outside the sandbox, which only gets a
+/// read-only registry; no user code runs during a fetch
+async fn fetch_dependencies(
+    project_path: &Path,
+    deadline: Instant,
+    on_event: &dyn Fn(CompileEvent),
+) -> Result<()> {
+    debug!("Fetching dependencies for sandboxed build in directory: {:?}", project_path);
+
+    let mut command = Command::new("cargo");
+    restrict_env(&mut command);
+    command.arg("fetch").current_dir(project_path);
+
+    let output = run_command(command, deadline, on_event)
+        .await
+        .context("Failed to execute cargo fetch")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Failed to fetch dependencies: {}", output.stderr));
+    }
+
+    Ok(())
+}
+
+/// What the response reports about a build's output
+#[derive(Default)]
+struct CompilationArtifacts {
+    wasm_size: Option<u64>,
+    code_hash: Option<String>,
+    abi: Option<serde_json::Value>,
+    wasm_report: Option<WasmReport>,
+    abi_warnings: Vec<AbiWarning>,
+    optimization: Option<OptimizationReport>,
+}
+
+/// WASM size, code hash, structure and ABI written by the build that started
+/// at `started`, after optimizing the WASM in place at `level`
+fn extract_compilation_artifacts(
+    project_path: &Path,
+    started: SystemTime,
+    level: OptimizationLevel,
+) -> Result<CompilationArtifacts> {
+    let wasm_file = resolve_artifact(project_path, ArtifactKind::Wasm, Freshness::BuiltSince(started))?;
+    let mut wasm = fs::read(&wasm_file).with_context(|| format!("Failed to read {:?}", wasm_file))?;
+
+    let optimization = if level == OptimizationLevel::None {
         None
+    } else {
+        let (optimized, report) = optimize_wasm(&wasm, level);
+        match optimized {
+            Some(optimized) => {
+                fs::write(&wasm_file, &optimized)
+                    .with_context(|| format!("Failed to write optimized {:?}", wasm_file))?;
+                info!(
+                    "Optimized {:?} from {} to {} bytes",
+                    wasm_file, report.original_size, report.optimized_size
+                );
+                wasm = optimized;
+            }
+            None => {
+                if let Some(note) = &report.note {
+                    warn!("Kept unoptimized WASM {:?}: {}", wasm_file, note);
+                }
+            }
+        }
+        Some(report)
     };
 
     // Read actual NEAR ABI file generated by cargo-near - NO FALLBACKS
-    let abi = if near_dir.exists() {
-        // Find ABI JSON files
-        let abi_files: Vec<_> = fs::read_dir(&near_dir)
-            .context("Failed to read near target directory")?
-            .filter_map(|entry| {
-                let entry = entry.ok()?;
-                let path = entry.path();
-                if path.extension()? == "json" && path.file_name()?.to_str()?.ends_with("_abi.json") {
-                    Some(path)
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        if let Some(abi_file) = abi_files.first() {
+    let abi = match resolve_artifact(project_path, ArtifactKind::Abi, Freshness::BuiltSince(started)) {
+        Ok(abi_file) => {
             info!("Reading NEAR ABI from: {:?}", abi_file);
-            match fs::read_to_string(abi_file) {
+            match fs::read_to_string(&abi_file) {
                 Ok(abi_content) => {
                     match serde_json::from_str::<serde_json::Value>(&abi_content) {
                         Ok(abi_json) => {
@@ -202,16 +435,80 @@ fn extract_compilation_artifacts(project_path: &PathBuf) -> Result<(Option<u64>,
                     None
                 }
             }
-        } else {
-            warn!("No NEAR ABI file found");
+        }
+        Err(e) => {
+            warn!("No NEAR ABI for this build: {}", e);
             None
         }
-    } else {
-        warn!("target/near directory not found");
-        None
     };
 
-    Ok((wasm_size, abi))
+    let wasm_report = match analyze_wasm(&wasm) {
+        Ok(report) => Some(report),
+        Err(e) => {
+            warn!("Failed to analyze WASM {:?}: {:#}", wasm_file, e);
+            None
+        }
+    };
+
+    let abi_warnings = match (&abi, &wasm_report) {
+        (Some(abi), Some(report)) => check_abi_exports(abi, report),
+        _ => Vec::new(),
+    };
+
+    Ok(CompilationArtifacts {
+        wasm_size: Some(wasm.len() as u64),
+        code_hash: Some(code_hash(&wasm)),
+        abi,
+        wasm_report,
+        abi_warnings,
+        optimization,
+    })
 }
 
-// Removed - no longer needed since we only read actual ABI files
\ No newline at end of file
+// Removed - no longer needed since we only read actual ABI files
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::sandbox::tests::unconfined;
+
+    /// Whether the toolchain has the standard library for contracts
+    fn wasm_target_installed() -> bool {
+        std::process::Command::new("rustc")
+            .args(["--print", "target-libdir", "--target", "wasm32-unknown-unknown"])
+            .output()
+            .is_ok_and(|output| {
+                output.status.success() && Path::new(String::from_utf8_lossy(&output.stdout).trim()).is_dir()
+            })
+    }
+
+    #[actix_rt::test]
+    async fn test_successful_build_reports_warnings() {
+        if !wasm_target_installed() {
+            eprintln!("skipping: the wasm32-unknown-unknown target is not installed");
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"contract\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("src/lib.rs"),
+            "pub fn answer() -> u32 {\n    let unused = 1;\n    42\n}\n",
+        )
+        .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(120);
+        let diagnostics = collect_diagnostics(&unconfined(), dir.path(), deadline).await.unwrap();
+        assert!(
+            diagnostics
+                .iter()
+                .any(|d| d.level == "warning" && d.message.contains("unused variable")),
+            "{:?}",
+            diagnostics
+        );
+    }
+}
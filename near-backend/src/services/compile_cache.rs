@@ -0,0 +1,310 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::models::CompileResponse;
+use crate::services::dependency_cache::touch;
+use crate::services::sandbox::dir_size;
+
+const DEFAULT_CACHE_DIR: &str = "compile_cache";
+const DEFAULT_MAX_MB: u64 = 1024;
+
+const RESPONSE_FILE: &str = "response.json";
+const ARTIFACTS_DIR: &str = "near";
+
+/// Stands in for the building project's directory in stored responses, so a
+/// hit shows the requesting project's paths rather than another user's
+const PROJECT_PLACEHOLDER: &str = "@@NEARPLAY_PROJECT@@";
+
+/// Results of successful builds keyed by a hash of everything that affects
+/// the output, so resubmitting identical code skips cargo entirely. Entries
+/// are shared between users with project paths scrubbed from their output.
+#[derive(Debug, Clone)]
+pub struct CompileCache {
+    root: PathBuf,
+    /// Disk budget; `None` disables the cache
+    max_bytes: Option<u64>,
+}
+
+impl CompileCache {
+    /// Read `COMPILE_CACHE_DIR` and `COMPILE_CACHE_MAX_MB`; a budget of 0 disables caching
+    pub fn from_env() -> Self {
+        let root = env::var("COMPILE_CACHE_DIR").unwrap_or_else(|_| DEFAULT_CACHE_DIR.to_string());
+        let max_mb = env::var("COMPILE_CACHE_MAX_MB")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MAX_MB);
+
+        Self {
+            root: PathBuf::from(root),
+            max_bytes: (max_mb > 0).then(|| max_mb * 1024 * 1024),
+        }
+    }
+
+    /// Stored response for `key`, with its artifacts restored into the
+    /// project's `target/near` so a later deploy picks up the same WASM
+    pub fn lookup(&self, key: &str, project_path: &Path) -> Result<Option<CompileResponse>> {
+        if self.max_bytes.is_none() {
+            return Ok(None);
+        }
+
+        let entry = self.root.join(key);
+        let Ok(response) = fs::read_to_string(entry.join(RESPONSE_FILE)) else {
+            return Ok(None);
+        };
+        let project = project_paths(project_path)[0].clone();
+        let mut response: CompileResponse = serde_json::from_str(&response.replace(PROJECT_PLACEHOLDER, &project))
+            .context("Failed to parse cached compile response")?;
+
+        let near_dir = project_path.join("target").join("near");
+        fs::create_dir_all(&near_dir).context("Failed to create target/near directory")?;
+        for file in artifact_files(&near_dir)? {
+            fs::remove_file(&file).with_context(|| format!("Failed to remove stale artifact {:?}", file))?;
+        }
+        for file in artifact_files(&entry.join(ARTIFACTS_DIR))? {
+            let target = near_dir.join(file.file_name().unwrap_or_default());
+            fs::copy(&file, &target).with_context(|| format!("Failed to restore cached artifact {:?}", file))?;
+        }
+
+        touch(&entry);
+        response.cache_hit = true;
+        info!("Compile cache hit for {}", key);
+        Ok(Some(response))
+    }
+
+    /// Save a successful build's response and artifacts under `key`
+    pub fn store(&self, key: &str, project_path: &Path, response: &CompileResponse) -> Result<()> {
+        let Some(max_bytes) = self.max_bytes else {
+            return Ok(());
+        };
+        let entry = self.root.join(key);
+        if entry.exists() {
+            return Ok(());
+        }
+
+        let staging = self.root.join(format!(".{}-{}", key, uuid::Uuid::new_v4()));
+        let written = write_entry(&staging, project_path, response)
+            .and_then(|_| fs::rename(&staging, &entry).context("Failed to publish compile cache entry"));
+        if let Err(e) = written {
+            let _ = fs::remove_dir_all(&staging);
+            // Losing the race to an identical build is fine
+            if !entry.exists() {
+                return Err(e);
+            }
+            return Ok(());
+        }
+
+        debug!("Stored compile cache entry {}", key);
+        self.evict(max_bytes);
+        Ok(())
+    }
+
+    /// Drop least recently used entries until the cache fits its budget
+    fn evict(&self, max_bytes: u64) {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return;
+        };
+        let mut entries: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|entry| {
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((modified, dir_size(&entry.path()), entry.path()))
+            })
+            .collect();
+        entries.sort();
+
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        for (_, size, path) in entries {
+            if total <= max_bytes {
+                break;
+            }
+            info!("Evicting compile cache entry {:?}", path);
+            match fs::remove_dir_all(&path) {
+                Ok(()) => total -= size,
+                Err(e) => warn!("Failed to evict compile cache entry {:?}: {}", path, e),
+            }
+        }
+    }
+}
+
+/// Hash of the project inputs that determine a build's result: every file
+/// outside `target` and `.git` (sources, manifest, lockfile), the toolchain
+/// and the build mode
+pub fn input_hash(project_path: &Path, toolchain: &str, build_mode: &str) -> Result<String> {
+    let mut files = Vec::new();
+    collect_inputs(project_path, Path::new(""), &mut files)
+        .context("Failed to read project files for cache key")?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(toolchain.as_bytes());
+    hasher.update([0]);
+    hasher.update(build_mode.as_bytes());
+    for relative in files {
+        let contents = fs::read(project_path.join(&relative))
+            .with_context(|| format!("Failed to read {:?}", relative))?;
+        hasher.update([0]);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn collect_inputs(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if relative.as_os_str().is_empty() && (name == "target" || name == ".git") {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_inputs(root, &relative.join(&name), files)?;
+        } else if file_type.is_file() {
+            files.push(relative.join(&name));
+        }
+    }
+    Ok(())
+}
+
+fn write_entry(staging: &Path, project_path: &Path, response: &CompileResponse) -> Result<()> {
+    let artifacts = staging.join(ARTIFACTS_DIR);
+    fs::create_dir_all(&artifacts).context("Failed to create compile cache entry")?;
+
+    for file in artifact_files(&project_path.join("target").join("near"))? {
+        fs::copy(&file, artifacts.join(file.file_name().unwrap_or_default()))
+            .with_context(|| format!("Failed to cache artifact {:?}", file))?;
+    }
+    let mut contents = serde_json::to_string(response)?;
+    for path in project_paths(project_path) {
+        contents = contents.replace(&path, PROJECT_PLACEHOLDER);
+    }
+    fs::write(staging.join(RESPONSE_FILE), contents).context("Failed to write cached compile response")?;
+    Ok(())
+}
+
+/// Ways build output names a project directory: absolute, as cargo prints
+/// it, then as given. Longest first so a path is never partly replaced.
+fn project_paths(project_path: &Path) -> Vec<String> {
+    let given = project_path.to_string_lossy().into_owned();
+    match fs::canonicalize(project_path) {
+        Ok(absolute) if absolute != project_path => vec![absolute.to_string_lossy().into_owned(), given],
+        _ => vec![given],
+    }
+}
+
+/// Build outputs directly inside a cargo-near output directory
+fn artifact_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        let is_artifact = matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("wasm" | "json" | "zst")
+        );
+        if is_artifact && path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CompileDetails;
+
+    fn response() -> CompileResponse {
+        CompileResponse {
+            success: true,
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: "Finished".to_string(),
+            details: CompileDetails {
+                status: "success".to_string(),
+                compilation_time: 12.5,
+                project_path: "projects/user/project".to_string(),
+                wasm_size: Some(4),
+                code_hash: None,
+                optimized: false,
+            },
+            abi: None,
+            refused_dependencies: Vec::new(),
+            diagnostics: Vec::new(),
+            cache_hit: false,
+            build_environment: None,
+            wasm_report: None,
+            abi_warnings: Vec::new(),
+            storage_estimate: None,
+            optimization: None,
+            version_changes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_input_hash_ignores_build_output() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "pub fn a() {}").unwrap();
+        let hash = input_hash(dir.path(), "rustc 1.86.0", "non-reproducible-wasm").unwrap();
+
+        fs::create_dir_all(dir.path().join("target/near")).unwrap();
+        fs::write(dir.path().join("target/near/contract.wasm"), "wasm").unwrap();
+        assert_eq!(hash, input_hash(dir.path(), "rustc 1.86.0", "non-reproducible-wasm").unwrap());
+
+        assert_ne!(hash, input_hash(dir.path(), "rustc 1.87.0", "non-reproducible-wasm").unwrap());
+        assert_ne!(hash, input_hash(dir.path(), "rustc 1.86.0", "reproducible-wasm").unwrap());
+        fs::write(dir.path().join("src/lib.rs"), "pub fn b() {}").unwrap();
+        assert_ne!(hash, input_hash(dir.path(), "rustc 1.86.0", "non-reproducible-wasm").unwrap());
+    }
+
+    #[test]
+    fn test_hit_restores_artifacts_and_evicts_by_budget() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let built = tempfile::tempdir().unwrap();
+        let fresh = tempfile::tempdir().unwrap();
+        fs::create_dir_all(built.path().join("target/near")).unwrap();
+        fs::write(built.path().join("target/near/contract.wasm"), "wasm").unwrap();
+
+        let cache = CompileCache {
+            root: cache_dir.path().to_path_buf(),
+            max_bytes: Some(1024 * 1024),
+        };
+        assert!(cache.lookup("abc", fresh.path()).unwrap().is_none());
+        let built_path = fs::canonicalize(built.path()).unwrap();
+        let mut built_response = response();
+        built_response.stderr = format!("   Compiling contract v0.1.0 ({})", built_path.display());
+        cache.store("abc", built.path(), &built_response).unwrap();
+
+        // Another project's hit shows its own paths
+        let hit = cache.lookup("abc", fresh.path()).unwrap().unwrap();
+        assert!(hit.cache_hit);
+        let fresh_path = fs::canonicalize(fresh.path()).unwrap();
+        assert_eq!(hit.stderr, format!("   Compiling contract v0.1.0 ({})", fresh_path.display()));
+        assert_eq!(
+            fs::read(fresh.path().join("target/near/contract.wasm")).unwrap(),
+            b"wasm"
+        );
+
+        // A budget smaller than one entry keeps nothing
+        let cache = CompileCache {
+            max_bytes: Some(1),
+            ..cache
+        };
+        cache.store("def", built.path(), &response()).unwrap();
+        assert!(cache.lookup("abc", fresh.path()).unwrap().is_none());
+        assert!(cache.lookup("def", fresh.path()).unwrap().is_none());
+    }
+}
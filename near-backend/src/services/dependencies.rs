@@ -0,0 +1,467 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table};
+
+use crate::models::{DependencyChange, RefusedDependency};
+
+/// Manifest tables whose entries are checked against the allowlist
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "build-dependencies", "dev-dependencies"];
+
+/// Manifest tables that could redirect crate sources and are only accepted
+/// as the base project has them. `[workspace]` counts: inherited
+/// `[workspace.dependencies]` entries carry their own source.
+const FORBIDDEN_TABLES: [&str; 3] = ["patch", "replace", "workspace"];
+
+/// Keys that point a dependency at something other than crates.io, or at an
+/// entry defined elsewhere in the manifest
+const SOURCE_KEYS: [&str; 6] = ["git", "path", "registry", "registry-index", "package", "workspace"];
+
+/// Crates users may add to their projects and the versions they may request.
+/// The first version listed is used when a request does not specify one.
+#[derive(Debug, Clone)]
+pub struct DependencyAllowlist {
+    crates: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for DependencyAllowlist {
+    fn default() -> Self {
+        let crates = [
+            ("near-contract-standards", vec!["5.5.0"]),
+            ("uint", vec!["0.10.0", "0.9.5"]),
+            ("hex", vec!["0.4.3"]),
+            ("bs58", vec!["0.5.1"]),
+            ("base64", vec!["0.22.1"]),
+            ("borsh", vec!["1.5.1"]),
+            ("serde", vec!["1.0"]),
+            ("serde_json", vec!["1.0"]),
+            ("schemars", vec!["0.8"]),
+        ];
+
+        Self {
+            crates: crates
+                .into_iter()
+                .map(|(name, versions)| {
+                    (name.to_string(), versions.into_iter().map(String::from).collect())
+                })
+                .collect(),
+        }
+    }
+}
+
+impl DependencyAllowlist {
+    /// Load the allowlist from the JSON file named by `DEPENDENCY_ALLOWLIST`
+    /// (`{"crate": ["version", ...]}`), falling back to the built-in list.
+    pub fn from_env() -> Result<Self> {
+        match env::var("DEPENDENCY_ALLOWLIST") {
+            Ok(path) => {
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read dependency allowlist: {}", path))?;
+                let crates = serde_json::from_str(&content)
+                    .context("Failed to parse dependency allowlist JSON")?;
+                Ok(Self { crates })
+            }
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Pick the version to write for `name`, or explain why it is refused
+    fn resolve_version(&self, name: &str, requested: Option<&str>) -> Result<String, String> {
+        let versions = self
+            .crates
+            .get(name)
+            .ok_or_else(|| format!("crate '{}' is not on the allowlist", name))?;
+
+        match requested {
+            None => versions
+                .first()
+                .cloned()
+                .ok_or_else(|| format!("no versions of '{}' are allowed", name)),
+            Some(requested) => {
+                let normalized = requested.trim().trim_start_matches(['^', '=']);
+                versions
+                    .iter()
+                    .find(|allowed| allowed.as_str() == normalized)
+                    .cloned()
+                    .ok_or_else(|| {
+                        format!(
+                            "version '{}' of '{}' is not allowed (allowed: {})",
+                            requested,
+                            name,
+                            versions.join(", ")
+                        )
+                    })
+            }
+        }
+    }
+}
+
+/// Apply the requested dependency changes to the project's Cargo.toml and
+/// drop any dependency the allowlist does not permit, including ones that
+/// arrived through a user-submitted manifest. Entries identical to the base
+/// project's are always kept.
+pub fn apply_dependency_changes(
+    project_path: &Path,
+    base_project_path: &Path,
+    changes: &[DependencyChange],
+    allowlist: &DependencyAllowlist,
+) -> Result<Vec<RefusedDependency>> {
+    let manifest_path = project_path.join("Cargo.toml");
+    let content = fs::read_to_string(&manifest_path).context("Failed to read project Cargo.toml")?;
+    let mut manifest: DocumentMut = content.parse().context("Failed to parse project Cargo.toml")?;
+
+    let base_content = fs::read_to_string(base_project_path.join("Cargo.toml"))
+        .context("Failed to read base project Cargo.toml")?;
+    let base_manifest: DocumentMut = base_content
+        .parse()
+        .context("Failed to parse base project Cargo.toml")?;
+
+    let mut refused = Vec::new();
+
+    for change in changes {
+        let refuse = |reason: String| RefusedDependency {
+            name: change.name.clone(),
+            version: change.version.clone(),
+            reason,
+        };
+
+        if is_base_dependency(&base_manifest, &change.name) {
+            refused.push(refuse("dependency is managed by the base project".to_string()));
+            continue;
+        }
+
+        if change.remove {
+            if let Some(dependencies) = manifest
+                .get_mut("dependencies")
+                .and_then(Item::as_table_like_mut)
+            {
+                dependencies.remove(&change.name);
+            }
+            debug!("Removed dependency {}", change.name);
+            continue;
+        }
+
+        if let Some(feature) = change.features.iter().find(|feature| !is_valid_feature(feature)) {
+            refused.push(refuse(format!("invalid feature name '{}'", feature)));
+            continue;
+        }
+
+        let version = match allowlist.resolve_version(&change.name, change.version.as_deref()) {
+            Ok(version) => version,
+            Err(reason) => {
+                refused.push(refuse(reason));
+                continue;
+            }
+        };
+
+        let dependencies = manifest
+            .entry("dependencies")
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_like_mut()
+            .context("[dependencies] in Cargo.toml is not a table")?;
+        dependencies.insert(&change.name, dependency_item(&version, &change.features));
+        debug!("Set dependency {} = {}", change.name, version);
+    }
+
+    refused.extend(sanitize_manifest(&mut manifest, &base_manifest, allowlist));
+
+    let updated = manifest.to_string();
+    if updated != content {
+        fs::write(&manifest_path, updated).context("Failed to write project Cargo.toml")?;
+        info!("Updated dependencies in {:?}", manifest_path);
+    }
+
+    for refusal in &refused {
+        warn!("Refused dependency {}: {}", refusal.name, refusal.reason);
+    }
+
+    Ok(refused)
+}
+
+/// Remove every manifest entry the allowlist does not permit. Overrides in
+/// `.cargo/config.toml` never reach the build: project files reserve and
+/// remove cargo configuration.
+fn sanitize_manifest(
+    manifest: &mut DocumentMut,
+    base_manifest: &DocumentMut,
+    allowlist: &DependencyAllowlist,
+) -> Vec<RefusedDependency> {
+    let mut refused = Vec::new();
+
+    for table in FORBIDDEN_TABLES {
+        let base_item = base_manifest.get(table);
+        if manifest
+            .get(table)
+            .is_some_and(|item| base_item.is_some_and(|base_item| same_entry(base_item, item)))
+        {
+            continue;
+        }
+        if manifest.remove(table).is_some() {
+            refused.push(RefusedDependency {
+                name: format!("[{}]", table),
+                version: None,
+                reason: "source overrides are not allowed".to_string(),
+            });
+        }
+    }
+
+    if manifest.remove("target").is_some() {
+        refused.push(RefusedDependency {
+            name: "[target]".to_string(),
+            version: None,
+            reason: "target-specific dependencies are not allowed".to_string(),
+        });
+    }
+
+    for table_name in DEPENDENCY_TABLES {
+        let base_table = base_manifest.get(table_name).and_then(Item::as_table_like);
+        let Some(table) = manifest.get_mut(table_name).and_then(Item::as_table_like_mut) else {
+            continue;
+        };
+
+        let mut rejected = Vec::new();
+        for (name, item) in table.iter() {
+            let base_item = base_table.and_then(|base| base.get(name));
+            if base_item.is_some_and(|base_item| same_entry(base_item, item)) {
+                continue;
+            }
+
+            let version = dependency_version(item);
+            let reason = if SOURCE_KEYS.iter().any(|key| item.get(key).is_some()) {
+                Some("only crates.io dependencies are allowed".to_string())
+            } else {
+                allowlist.resolve_version(name, version.as_deref()).err()
+            };
+
+            if let Some(reason) = reason {
+                rejected.push(RefusedDependency {
+                    name: name.to_string(),
+                    version,
+                    reason,
+                });
+            }
+        }
+
+        for refusal in &rejected {
+            table.remove(&refusal.name);
+        }
+        refused.extend(rejected);
+    }
+
+    refused
+}
+
+fn is_base_dependency(base_manifest: &DocumentMut, name: &str) -> bool {
+    DEPENDENCY_TABLES.iter().any(|table| {
+        base_manifest
+            .get(table)
+            .and_then(Item::as_table_like)
+            .is_some_and(|table| table.contains_key(name))
+    })
+}
+
+fn dependency_item(version: &str, features: &[String]) -> Item {
+    if features.is_empty() {
+        return toml_edit::value(version);
+    }
+
+    let mut table = InlineTable::new();
+    table.insert("version", version.into());
+    table.insert("features", features.iter().collect::<Array>().into());
+    toml_edit::value(table)
+}
+
+fn dependency_version(item: &Item) -> Option<String> {
+    item.as_str()
+        .or_else(|| item.get("version").and_then(Item::as_str))
+        .map(String::from)
+}
+
+fn same_entry(a: &Item, b: &Item) -> bool {
+    let normalize = |item: &Item| {
+        item.to_string()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+    };
+    normalize(a) == normalize(b)
+}
+
+fn is_valid_feature(feature: &str) -> bool {
+    !feature.is_empty()
+        && feature
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ServiceError;
+    use crate::services::project_files::{apply_file_changes, remove_build_config};
+
+    const BASE_MANIFEST: &str = r#"[package]
+name = "contract"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+near-sdk = "5.5"
+
+[dev-dependencies]
+near-sdk = { version = "5.5", features = ["unit-testing"] }
+"#;
+
+    fn setup(manifest: &str) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("base")).unwrap();
+        fs::create_dir_all(dir.path().join("project")).unwrap();
+        fs::write(dir.path().join("base/Cargo.toml"), BASE_MANIFEST).unwrap();
+        fs::write(dir.path().join("project/Cargo.toml"), manifest).unwrap();
+        dir
+    }
+
+    fn change(name: &str, version: Option<&str>) -> DependencyChange {
+        DependencyChange {
+            name: name.to_string(),
+            version: version.map(String::from),
+            features: Vec::new(),
+            remove: false,
+        }
+    }
+
+    #[test]
+    fn test_apply_allowed_and_refused_changes() {
+        let dir = setup(BASE_MANIFEST);
+        let project = dir.path().join("project");
+
+        let mut standards = change("near-contract-standards", None);
+        standards.features = vec!["abi".to_string()];
+        let changes = vec![
+            standards,
+            change("hex", Some("0.4.3")),
+            change("uint", Some("0.1.0")),
+            change("openssl", None),
+            change("near-sdk", Some("4.0.0")),
+        ];
+
+        let refused = apply_dependency_changes(
+            &project,
+            &dir.path().join("base"),
+            &changes,
+            &DependencyAllowlist::default(),
+        )
+        .unwrap();
+
+        let refused_names: Vec<_> = refused.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(refused_names, vec!["uint", "openssl", "near-sdk"]);
+
+        let manifest: DocumentMut = fs::read_to_string(project.join("Cargo.toml"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(manifest["dependencies"]["hex"].as_str(), Some("0.4.3"));
+        assert_eq!(
+            manifest["dependencies"]["near-contract-standards"]["version"].as_str(),
+            Some("5.5.0")
+        );
+        assert_eq!(manifest["dependencies"]["near-sdk"].as_str(), Some("5.5"));
+    }
+
+    #[test]
+    fn test_submitted_manifest_is_sanitized() {
+        let dir = setup(
+            r#"[package]
+name = "contract"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+near-sdk = "5.5"
+hex = "0.4.3"
+evil = { git = "https://example.com/evil.git" }
+serde = { version = "1.0", path = "../../../etc" }
+
+[patch.crates-io]
+near-sdk = { path = "/tmp/near-sdk" }
+"#,
+        );
+        let project = dir.path().join("project");
+
+        let refused = apply_dependency_changes(
+            &project,
+            &dir.path().join("base"),
+            &[],
+            &DependencyAllowlist::default(),
+        )
+        .unwrap();
+
+        let refused_names: Vec<_> = refused.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(refused_names, vec!["[patch]", "evil", "serde"]);
+
+        let manifest: DocumentMut = fs::read_to_string(project.join("Cargo.toml"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(manifest.get("patch").is_none());
+        assert!(manifest["dependencies"].get("evil").is_none());
+        assert_eq!(manifest["dependencies"]["hex"].as_str(), Some("0.4.3"));
+    }
+
+    #[test]
+    fn test_workspace_dependencies_are_refused() {
+        let dir = setup(
+            r#"[package]
+name = "contract"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+near-sdk = "5.5"
+hex = { workspace = true }
+
+[workspace.dependencies]
+hex = { git = "https://example.com/evil.git" }
+"#,
+        );
+        let project = dir.path().join("project");
+
+        let refused = apply_dependency_changes(
+            &project,
+            &dir.path().join("base"),
+            &[],
+            &DependencyAllowlist::default(),
+        )
+        .unwrap();
+
+        let refused_names: Vec<_> = refused.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(refused_names, vec!["[workspace]", "hex"]);
+
+        let manifest: DocumentMut = fs::read_to_string(project.join("Cargo.toml"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(manifest.get("workspace").is_none());
+        assert!(manifest["dependencies"].get("hex").is_none());
+    }
+
+    #[test]
+    fn test_cargo_config_patch_is_refused() {
+        let dir = setup(BASE_MANIFEST);
+        let project = dir.path().join("project");
+        let config = "[patch.crates-io]\nnear-sdk = { path = \"/tmp/near-sdk\" }\n";
+
+        let files = BTreeMap::from([(".cargo/config.toml".to_string(), config.to_string())]);
+        let error = apply_file_changes(&project, &files, &[]).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(ServiceError::InvalidFilePath { .. })));
+
+        // Left over from before cargo configuration was reserved
+        fs::create_dir_all(project.join(".cargo")).unwrap();
+        fs::write(project.join(".cargo/config.toml"), config).unwrap();
+        remove_build_config(&project).unwrap();
+        assert!(!project.join(".cargo").exists());
+    }
+}
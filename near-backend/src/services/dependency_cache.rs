@@ -0,0 +1,337 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::services::sandbox::restrict_env;
+use crate::services::toolchains::VersionSelection;
+
+const DEFAULT_CACHE_DIR: &str = "dependency_cache";
+const DEFAULT_MAX_ENTRIES: usize = 8;
+
+/// Part of every key; bumped to retire entries that earlier versions may
+/// have stored from user builds
+const KEY_VERSION: &str = "base-v2";
+
+/// Directories under a profile that hold per-crate artifacts
+const ARTIFACT_DIRS: [&str; 3] = ["deps", ".fingerprint", "build"];
+
+/// Compiled dependencies shared between projects. Each entry is a base
+/// project's `target` directory keyed by toolchain and `Cargo.lock`, stripped
+/// of the base crate and final artifacts, and copied into new projects so
+/// their first build only compiles the contract itself. Entries only ever
+/// come from the server's own base builds, never from user builds, and their
+/// files are read-only.
+#[derive(Debug, Clone)]
+pub struct DependencyCache {
+    root: PathBuf,
+    max_entries: usize,
+}
+
+impl DependencyCache {
+    /// Read `DEPENDENCY_CACHE_DIR` and `DEPENDENCY_CACHE_MAX_ENTRIES`
+    pub fn from_env() -> Self {
+        let root = env::var("DEPENDENCY_CACHE_DIR").unwrap_or_else(|_| DEFAULT_CACHE_DIR.to_string());
+        let max_entries = env::var("DEPENDENCY_CACHE_MAX_ENTRIES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MAX_ENTRIES);
+
+        Self {
+            root: PathBuf::from(root),
+            max_entries,
+        }
+    }
+
+    /// Copy cached dependencies into a project that has never been built
+    /// with `toolchain`, as reported by [`toolchain_version`]. Returns
+    /// whether a matching entry was found.
+    pub fn seed(&self, project_path: &Path, toolchain: &str) -> Result<bool> {
+        let target = project_path.join("target");
+        if target.exists() {
+            return Ok(false);
+        }
+
+        let key = project_cache_key(project_path, toolchain);
+        let entry = self.root.join(&key);
+        if !entry.is_dir() {
+            debug!("No dependency cache entry {} for {:?}", key, project_path);
+            return Ok(false);
+        }
+
+        copy_tree(&entry, &target, &|_, _| false, false).context("Failed to seed target directory")?;
+        touch(&entry);
+        info!("Seeded {:?} from dependency cache entry {}", project_path, key);
+        Ok(true)
+    }
+
+    /// Save a base project's dependencies unless an entry for its toolchain
+    /// and lockfile already exists. Only for trusted builds: user builds run
+    /// arbitrary build scripts that could plant artifacts for others.
+    pub fn store(&self, project_path: &Path, toolchain: &str) -> Result<()> {
+        let key = project_cache_key(project_path, toolchain);
+        let entry = self.root.join(&key);
+        if entry.is_dir() {
+            return Ok(());
+        }
+
+        let crate_names = project_crate_names(project_path);
+        let staging = self.root.join(format!(".{}-{}", key, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&self.root).context("Failed to create dependency cache directory")?;
+
+        let skip = |relative: &Path, is_dir| is_project_specific(relative, is_dir, &crate_names);
+        let copied = copy_tree(&project_path.join("target"), &staging, &skip, true);
+        // Another build may have stored the same key in the meantime
+        let stored = copied.and_then(|_| {
+            fs::rename(&staging, &entry).context("Failed to publish dependency cache entry")
+        });
+        if let Err(e) = stored {
+            let _ = fs::remove_dir_all(&staging);
+            if !entry.is_dir() {
+                return Err(e);
+            }
+            return Ok(());
+        }
+
+        info!("Stored dependency cache entry {} from {:?}", key, project_path);
+        self.evict();
+        Ok(())
+    }
+
+    /// Drop least recently used entries beyond the configured count
+    fn evict(&self) {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return;
+        };
+        let mut entries: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .collect();
+        if entries.len() <= self.max_entries {
+            return;
+        }
+
+        entries.sort();
+        let excess = entries.len() - self.max_entries;
+        for (_, path) in entries.into_iter().take(excess) {
+            info!("Evicting dependency cache entry {:?}", path);
+            if let Err(e) = fs::remove_dir_all(&path) {
+                warn!("Failed to evict dependency cache entry {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+/// Cache key for a project: its toolchain and the exact contents of its
+/// `Cargo.lock`
+fn project_cache_key(project_path: &Path, toolchain: &str) -> String {
+    let lockfile = fs::read(project_path.join("Cargo.lock")).unwrap_or_default();
+    cache_key(toolchain, &lockfile)
+}
+
+/// `rustc -vV` for the server's selection, run in its base project so files
+/// a user uploaded cannot pick the answer
+pub async fn toolchain_version(versions: &VersionSelection) -> Result<String> {
+    let mut command = Command::new("rustc");
+    restrict_env(&mut command);
+    if let Some(toolchain) = &versions.toolchain {
+        command.env("RUSTUP_TOOLCHAIN", toolchain);
+    }
+    let output = command
+        .arg("-vV")
+        .current_dir(versions.base_project_path())
+        .output()
+        .await
+        .context("Failed to query rustc version")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to query rustc version: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn cache_key(toolchain: &str, lockfile: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_VERSION.as_bytes());
+    hasher.update([0]);
+    hasher.update(toolchain.as_bytes());
+    hasher.update([0]);
+    hasher.update(lockfile);
+    hasher
+        .finalize()
+        .iter()
+        .take(16)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Package name and crate name of the project's own crate
+fn project_crate_names(project_path: &Path) -> Vec<String> {
+    let name = fs::read_to_string(project_path.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| manifest.parse::<toml_edit::DocumentMut>().ok())
+        .and_then(|manifest| manifest.get("package")?.get("name")?.as_str().map(str::to_string));
+
+    match name {
+        Some(name) => {
+            let crate_name = name.replace('-', "_");
+            if crate_name == name {
+                vec![name]
+            } else {
+                vec![name, crate_name]
+            }
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Whether a path under `target` belongs to the project rather than its
+/// dependencies: cargo-near output, incremental state, final artifacts at
+/// the top of a profile directory and the project crate's own units
+fn is_project_specific(relative: &Path, is_dir: bool, crate_names: &[String]) -> bool {
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    let Some(name) = components.last() else {
+        return false;
+    };
+
+    if components[0] == "near" || components.iter().any(|component| component == "incremental") {
+        return true;
+    }
+
+    let parent = components.len().checked_sub(2).map(|index| components[index].as_ref());
+    match parent {
+        Some(parent) if ARTIFACT_DIRS.contains(&parent) => crate_names.iter().any(|crate_name| {
+            name.starts_with(&format!("{}-", crate_name))
+                || name.starts_with(&format!("lib{}-", crate_name))
+        }),
+        // `<profile>/<file>` or `<triple>/<profile>/<file>`
+        _ => !is_dir && (2..=3).contains(&components.len()),
+    }
+}
+
+/// Recursively copy `src` to `dst`, preserving modification times so cargo
+/// keeps treating the copied units as fresh. Copied files are made read-only
+/// or owner-writable as `read_only` asks.
+fn copy_tree(src: &Path, dst: &Path, skip: &dyn Fn(&Path, bool) -> bool, read_only: bool) -> Result<()> {
+    fn walk(
+        src: &Path,
+        dst: &Path,
+        relative: &Path,
+        skip: &dyn Fn(&Path, bool) -> bool,
+        read_only: bool,
+    ) -> Result<()> {
+        fs::create_dir_all(dst.join(relative))?;
+        for entry in fs::read_dir(src.join(relative))? {
+            let entry = entry?;
+            let relative = relative.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if skip(&relative, file_type.is_dir()) {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                walk(src, dst, &relative, skip, read_only)?;
+            } else if file_type.is_file() {
+                let target = dst.join(&relative);
+                fs::copy(entry.path(), &target)?;
+                let metadata = entry.metadata()?;
+                // Writable while the timestamp is restored
+                fs::set_permissions(&target, copy_permissions(&metadata, false))?;
+                fs::File::options().write(true).open(&target)?.set_modified(metadata.modified()?)?;
+                fs::set_permissions(&target, copy_permissions(&metadata, read_only))?;
+            }
+        }
+        Ok(())
+    }
+
+    walk(src, dst, Path::new(""), skip, read_only)
+        .with_context(|| format!("Failed to copy {:?} to {:?}", src, dst))
+}
+
+/// Permissions for a copy of a file: read-only or owner-writable, keeping
+/// its executable bits where the platform has them
+fn copy_permissions(metadata: &fs::Metadata, read_only: bool) -> fs::Permissions {
+    let mut permissions = metadata.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        permissions.set_mode(if read_only { 0o444 } else { 0o644 } | (metadata.mode() & 0o111));
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(read_only);
+    permissions
+}
+
+/// Mark a cache entry as recently used for eviction
+pub fn touch(entry: &Path) {
+    if let Err(e) = fs::File::open(entry).and_then(|dir| dir.set_modified(std::time::SystemTime::now())) {
+        debug!("Failed to touch cache entry {:?}: {}", entry, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_specific_artifacts_are_excluded() {
+        let names = vec!["hello-near".to_string(), "hello_near".to_string()];
+        let file = |path: &str| is_project_specific(Path::new(path), false, &names);
+        let dir = |path: &str| is_project_specific(Path::new(path), true, &names);
+
+        assert!(dir("near"));
+        assert!(file("wasm32-unknown-unknown/release/hello_near.wasm"));
+        assert!(file("release/libhello_near.rlib"));
+        assert!(dir("release/incremental"));
+        assert!(file("wasm32-unknown-unknown/release/deps/hello_near-1a2b.wasm"));
+        assert!(dir("wasm32-unknown-unknown/release/.fingerprint/hello-near-1a2b"));
+
+        assert!(!file(".rustc_info.json"));
+        assert!(!dir("wasm32-unknown-unknown/release"));
+        assert!(!dir("wasm32-unknown-unknown/release/deps"));
+        assert!(!file("wasm32-unknown-unknown/release/deps/libnear_sdk-3c4d.rlib"));
+        assert!(!file("release/build/serde-5e6f/output"));
+        assert!(!file("release/.fingerprint/serde-5e6f/lib-serde"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stored_entries_are_read_only_and_seeds_writable() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("target");
+        let entry = dir.path().join("entry");
+        let seeded = dir.path().join("seeded");
+        fs::create_dir_all(project.join("release/deps")).unwrap();
+        fs::write(project.join("release/deps/libserde-1a2b.rlib"), b"rlib").unwrap();
+
+        let mode = |target: &Path| {
+            let rlib = target.join("release/deps/libserde-1a2b.rlib");
+            fs::metadata(rlib).unwrap().mode() & 0o777
+        };
+        copy_tree(&project, &entry, &|_, _| false, true).unwrap();
+        assert_eq!(mode(&entry), 0o444);
+        copy_tree(&entry, &seeded, &|_, _| false, false).unwrap();
+        assert_eq!(mode(&seeded), 0o644);
+    }
+
+    #[test]
+    fn test_cache_key_tracks_toolchain_and_lockfile() {
+        let key = cache_key("rustc 1.86.0", b"lock");
+        assert_eq!(key.len(), 32);
+        assert_eq!(key, cache_key("rustc 1.86.0", b"lock"));
+        assert_ne!(key, cache_key("rustc 1.87.0", b"lock"));
+        assert_ne!(key, cache_key("rustc 1.86.0", b"lock2"));
+    }
+}
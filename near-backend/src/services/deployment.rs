@@ -1,9 +1,10 @@
 use anyhow::{Context, Result};
 use log::{info, warn, error};
 use std::fs;
-use std::path::PathBuf;
 use std::env;
-use crate::models::{DeployDetails, DeployResponse};
+use crate::models::{DeployDetails, DeployResponse, ProjectId, UserId};
+use crate::services::artifacts::{project_dir, resolve_artifact, ArtifactKind, Freshness};
+use crate::services::storage_cost::{format_near, StoragePricing, PROOF_TRANSFER_AMOUNT};
 use near_jsonrpc_client::{methods, JsonRpcClient};
 use near_jsonrpc_primitives::types::query::QueryResponseKind;
 use near_primitives::types::AccountId;
@@ -13,10 +14,25 @@ use std::str::FromStr;
 
 const TESTNET_RPC_URL: &str = "https://rpc.testnet.near.org";
 
+/// yoctoNEAR a deployment of the project's current WASM transfers to the new
+/// account: storage for the code plus the configured margin, and the proof
+/// transfer. Matches the `recommended_funding` of its storage estimate.
+pub fn projected_funding(user_id: &UserId, project_id: &ProjectId) -> Result<u128> {
+    let wasm_path = resolve_artifact(&project_dir(user_id, project_id), ArtifactKind::Wasm, Freshness::NewerThanSources)?;
+    let wasm_size = fs::metadata(&wasm_path).context("Failed to read WASM file")?.len();
+    StoragePricing::from_env()?.deploy_funding(wasm_size)
+}
+
+/// Deploy the project's WASM to a new subaccount funded with `funding` from
+/// [`projected_funding`]. `on_funding_sent` runs just before the transaction
+/// that transfers it is broadcast, after which the funds may be spent even
+/// if the deployment fails.
 pub async fn deploy_contract(
-    user_id: &str,
-    project_id: &str,
+    user_id: &UserId,
+    project_id: &ProjectId,
     _account_id: Option<&str>,
+    funding: u128,
+    on_funding_sent: &mut dyn FnMut(),
 ) -> Result<DeployResponse> {
     info!(
         "Starting NEAR deployment for project {} by user {}",
@@ -39,7 +55,7 @@ pub async fn deploy_contract(
     let public_key = secret_key.public_key();
 
     // Find the project directory
-    let project_path = PathBuf::from("projects").join(user_id).join(project_id);
+    let project_path = project_dir(user_id, project_id);
     if !project_path.exists() {
         return Err(anyhow::anyhow!("Project directory not found: {:?}", project_path));
     }
@@ -61,30 +77,20 @@ pub async fn deploy_contract(
 
     info!("Deploying to subaccount: {} using NEAR JSON-RPC", subaccount_id);
 
-    // Find the compiled WASM file for deployment
-    let wasm_files: Vec<_> = fs::read_dir(&project_path.join("target").join("near"))
-        .context("Failed to read target/near directory")?
-        .filter_map(|entry| {
-            let entry = entry.ok()?;
-            let path = entry.path();
-            if path.extension()? == "wasm" {
-                Some(path)
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    if wasm_files.is_empty() {
-        return Err(anyhow::anyhow!("No WASM file found. Please compile the project first."));
-    }
-
-    let wasm_path = &wasm_files[0];
-    let wasm_code = fs::read(wasm_path)
+    // The project's own WASM, and only if it matches the current sources
+    let wasm_path = resolve_artifact(&project_path, ArtifactKind::Wasm, Freshness::NewerThanSources)?;
+    let wasm_code = fs::read(&wasm_path)
         .context("Failed to read WASM file")?;
 
     info!("WASM file loaded, size: {} bytes", wasm_code.len());
 
+    let storage_estimate = StoragePricing::from_env()?.estimate(wasm_code.len() as u64)?;
+    info!(
+        "Funding new account with {} NEAR ({} NEAR for storage)",
+        format_near(funding),
+        storage_estimate.storage_cost_near
+    );
+
     // Create JSON-RPC client
     let client = JsonRpcClient::connect(TESTNET_RPC_URL);
 
@@ -138,7 +144,7 @@ pub async fn deploy_contract(
                 }
             }),
             Action::Transfer(TransferAction {
-                deposit: 2_000_000_000_000_000_000_000_000, // 2.0 NEAR for storage and deployment
+                deposit: funding,
             }),
         ];
 
@@ -148,7 +154,7 @@ pub async fn deploy_contract(
             public_key: public_key.clone(),
             nonce,
             receiver_id: subaccount_id.clone(),
-            block_hash: block_hash.clone(),
+            block_hash,
             actions: create_actions,
         };
 
@@ -164,6 +170,7 @@ pub async fn deploy_contract(
         };
 
         info!("Broadcasting account creation transaction...");
+        on_funding_sent();
         let create_result = match client.call(create_request).await {
             Ok(result) => result,
             Err(e) => {
@@ -299,6 +306,8 @@ pub async fn deploy_contract(
             block_height,
             timestamp,
             deployer_account: parent_account_id.clone(),
+            funded_amount: funding.to_string(),
+            storage_estimate,
         },
     };
 
@@ -323,10 +332,7 @@ async fn check_account_exists(client: &JsonRpcClient, account_id: &str) -> bool
         },
     };
 
-    match client.call(request).await {
-        Ok(_) => true,
-        Err(_) => false,
-    }
+    client.call(request).await.is_ok()
 }
 
 async fn send_proof_transfer(
@@ -363,7 +369,7 @@ async fn send_proof_transfer(
     // Create transfer action
     let actions = vec![
         Action::Transfer(TransferAction {
-            deposit: 30_000_000_000_000_000_000_000, // 0.03 NEAR
+            deposit: PROOF_TRANSFER_AMOUNT,
         }),
     ];
 
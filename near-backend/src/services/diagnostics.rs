@@ -0,0 +1,139 @@
+use serde::Deserialize;
+
+use crate::models::{Diagnostic, DiagnosticSpan, DiagnosticSuggestion};
+
+/// Subset of a line of `cargo --message-format=json` output
+#[derive(Debug, Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<RustcDiagnostic>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RustcDiagnostic {
+    message: String,
+    code: Option<RustcCode>,
+    level: String,
+    spans: Vec<RustcSpan>,
+    #[serde(default)]
+    children: Vec<RustcDiagnostic>,
+    rendered: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RustcCode {
+    code: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RustcSpan {
+    file_name: String,
+    line_start: usize,
+    line_end: usize,
+    column_start: usize,
+    column_end: usize,
+    is_primary: bool,
+    label: Option<String>,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<String>,
+}
+
+impl From<&RustcSpan> for DiagnosticSpan {
+    fn from(span: &RustcSpan) -> Self {
+        DiagnosticSpan {
+            file: span.file_name.clone(),
+            line_start: span.line_start,
+            line_end: span.line_end,
+            column_start: span.column_start,
+            column_end: span.column_end,
+            is_primary: span.is_primary,
+            label: span.label.clone(),
+        }
+    }
+}
+
+/// `cargo check` arguments for the contract's wasm target with machine-readable output
+pub const CHECK_ARGS: [&str; 4] = [
+    "check",
+    "--message-format=json",
+    "--target",
+    "wasm32-unknown-unknown",
+];
+
+/// Extract compiler diagnostics from cargo's JSON message stream
+pub fn parse_cargo_messages(stdout: &str) -> Vec<Diagnostic> {
+    stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+        .filter(|message| message.reason == "compiler-message")
+        .filter_map(|message| message.message)
+        .filter(|diagnostic| !is_summary(diagnostic))
+        .map(|diagnostic| to_diagnostic(&diagnostic))
+        .collect()
+}
+
+fn to_diagnostic(diagnostic: &RustcDiagnostic) -> Diagnostic {
+    let mut notes = Vec::new();
+    let mut suggestions = Vec::new();
+
+    for child in &diagnostic.children {
+        notes.push(format!("{}: {}", child.level, child.message));
+        for span in &child.spans {
+            if let Some(replacement) = &span.suggested_replacement {
+                suggestions.push(DiagnosticSuggestion {
+                    message: child.message.clone(),
+                    span: span.into(),
+                    replacement: replacement.clone(),
+                    applicability: span.suggestion_applicability.clone(),
+                });
+            }
+        }
+    }
+
+    Diagnostic {
+        level: diagnostic.level.clone(),
+        code: diagnostic.code.as_ref().map(|code| code.code.clone()),
+        message: diagnostic.message.clone(),
+        spans: diagnostic.spans.iter().map(DiagnosticSpan::from).collect(),
+        notes,
+        suggestions,
+        rendered: diagnostic.rendered.clone(),
+    }
+}
+
+/// rustc's closing "aborting due to ..." / "N warnings emitted" lines carry no location
+fn is_summary(diagnostic: &RustcDiagnostic) -> bool {
+    diagnostic.spans.is_empty()
+        && diagnostic.code.is_none()
+        && (diagnostic.message.starts_with("aborting due to")
+            || diagnostic.message.ends_with("emitted"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECK_OUTPUT: &str = r#"{"reason":"compiler-artifact","package_id":"near-sdk 5.5.0","target":{"name":"near_sdk"},"fresh":true}
+{"reason":"compiler-message","package_id":"contract 0.1.0","message":{"rendered":"error[E0425]: cannot find value `greting` in this scope\n","$message_type":"diagnostic","children":[{"children":[],"code":null,"level":"help","message":"a field with a similar name exists","rendered":null,"spans":[{"byte_end":310,"byte_start":303,"column_end":21,"column_start":14,"expansion":null,"file_name":"src/lib.rs","is_primary":true,"label":null,"line_end":12,"line_start":12,"suggested_replacement":"self.greeting","suggestion_applicability":"MaybeIncorrect","text":[]}]}],"code":{"code":"E0425","explanation":null},"level":"error","message":"cannot find value `greting` in this scope","spans":[{"byte_end":310,"byte_start":303,"column_end":21,"column_start":14,"expansion":null,"file_name":"src/lib.rs","is_primary":true,"label":"not found in this scope","line_end":12,"line_start":12,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}]}}
+{"reason":"compiler-message","package_id":"contract 0.1.0","message":{"rendered":"error: aborting due to 1 previous error\n","$message_type":"diagnostic","children":[],"code":null,"level":"error","message":"aborting due to 1 previous error","spans":[]}}
+{"reason":"build-finished","success":false}"#;
+
+    #[test]
+    fn test_parse_cargo_messages() {
+        let diagnostics = parse_cargo_messages(CHECK_OUTPUT);
+        assert_eq!(diagnostics.len(), 1);
+
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.level, "error");
+        assert_eq!(diagnostic.code.as_deref(), Some("E0425"));
+        assert_eq!(diagnostic.spans[0].file, "src/lib.rs");
+        assert_eq!(diagnostic.spans[0].line_start, 12);
+        assert_eq!(diagnostic.spans[0].column_start, 14);
+        assert_eq!(diagnostic.notes, vec!["help: a field with a similar name exists"]);
+        assert_eq!(diagnostic.suggestions[0].replacement, "self.greeting");
+        assert_eq!(
+            diagnostic.suggestions[0].applicability.as_deref(),
+            Some("MaybeIncorrect")
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, info};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::task::AbortHandle;
+use uuid::Uuid;
+
+use crate::errors::{to_api_error, ServiceError};
+use crate::models::{ApiError, CompileEvent, CompileResponse, JobStatus, JobStatusResponse};
+use crate::services::build_queue::{BuildQueue, BuildTicket};
+use crate::services::project_locks::ProjectClaim;
+
+/// How long finished jobs stay queryable before they are pruned
+const FINISHED_JOB_TTL_MINUTES: i64 = 60;
+
+/// Events kept per job so late subscribers can replay the build log
+const MAX_EVENT_HISTORY: usize = 10_000;
+
+/// Capacity of the live event channel before slow subscribers start lagging
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+struct Job {
+    user_id: String,
+    project_id: String,
+    status: JobStatus,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    result: Option<CompileResponse>,
+    error: Option<ApiError>,
+    abort_handle: Option<AbortHandle>,
+    /// Build queue ticket while the job waits for a slot
+    queue_ticket: Option<u64>,
+    events: Vec<CompileEvent>,
+    sender: broadcast::Sender<CompileEvent>,
+}
+
+impl Job {
+    fn emit(&mut self, event: CompileEvent) {
+        if self.events.len() < MAX_EVENT_HISTORY || event.is_terminal() {
+            self.events.push(event.clone());
+        }
+        // Nobody listening is not an error
+        let _ = self.sender.send(event);
+    }
+
+    fn to_response(&self, job_id: Uuid, queue: &BuildQueue) -> JobStatusResponse {
+        JobStatusResponse {
+            job_id: job_id.to_string(),
+            user_id: self.user_id.clone(),
+            project_id: self.project_id.clone(),
+            status: self.status,
+            queue_position: self.queue_ticket.and_then(|ticket| queue.position(ticket)),
+            created_at: self.created_at,
+            started_at: self.started_at,
+            finished_at: self.finished_at,
+            result: self.result.clone(),
+            error: self.error.clone(),
+        }
+    }
+}
+
+/// In-memory registry of background compile jobs, shared across workers
+#[derive(Clone)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<Uuid, Job>>>,
+    queue: BuildQueue,
+}
+
+/// Handle given to a running job for publishing its build events
+#[derive(Clone)]
+pub struct JobEvents {
+    registry: JobRegistry,
+    job_id: Uuid,
+}
+
+impl JobEvents {
+    pub fn emit(&self, event: CompileEvent) {
+        if let Some(job) = self.registry.jobs.lock().unwrap().get_mut(&self.job_id) {
+            if !job.status.is_finished() {
+                job.emit(event);
+            }
+        }
+    }
+}
+
+/// Everything a new subscriber needs: the events so far and, while the job
+/// is still active, a receiver for the rest
+pub struct JobSubscription {
+    pub history: Vec<CompileEvent>,
+    pub receiver: Option<broadcast::Receiver<CompileEvent>>,
+}
+
+impl JobRegistry {
+    pub fn new(queue: BuildQueue) -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            queue,
+        }
+    }
+
+    /// Register a job that starts once it holds the project lock from `claim`
+    /// and `ticket` is admitted by the build queue, in that order so a job
+    /// waiting on its project never holds a build slot. Dropping the compile
+    /// future on cancellation kills the cargo process it spawned.
+    pub fn spawn<F, Fut>(
+        &self,
+        user_id: &str,
+        project_id: &str,
+        ticket: BuildTicket,
+        claim: ProjectClaim,
+        compile: F,
+    ) -> JobStatusResponse
+    where
+        F: FnOnce(JobEvents) -> Fut,
+        Fut: Future<Output = anyhow::Result<CompileResponse>> + 'static,
+    {
+        let job_id = Uuid::new_v4();
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let job = Job {
+            user_id: user_id.to_string(),
+            project_id: project_id.to_string(),
+            status: JobStatus::Queued,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            result: None,
+            error: None,
+            abort_handle: None,
+            queue_ticket: Some(ticket.id()),
+            events: vec![CompileEvent::Status {
+                status: JobStatus::Queued,
+            }],
+            sender,
+        };
+
+        let response = {
+            let mut jobs = self.jobs.lock().unwrap();
+            prune_finished(&mut jobs);
+            supersede_queued(&mut jobs, user_id, project_id);
+            let response = job.to_response(job_id, &self.queue);
+            jobs.insert(job_id, job);
+            response
+        };
+
+        let registry = self.clone();
+        let compile = compile(JobEvents {
+            registry: self.clone(),
+            job_id,
+        });
+        let handle = actix_web::rt::spawn(async move {
+            let result = match claim.lock().await {
+                Ok(_guard) => {
+                    let _permit = ticket.acquire().await;
+                    registry.mark_running(job_id);
+                    compile.await
+                }
+                Err(e) => Err(e.into()),
+            };
+            registry.finish(job_id, result);
+        });
+
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&job_id) {
+            job.abort_handle = Some(handle.abort_handle());
+        }
+
+        info!("Compile job {} queued for project {}", job_id, project_id);
+        response
+    }
+
+    pub fn get(&self, job_id: &str) -> Result<JobStatusResponse, ServiceError> {
+        let id = parse_job_id(job_id)?;
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(&id)
+            .map(|job| job.to_response(id, &self.queue))
+            .ok_or_else(|| ServiceError::JobNotFound {
+                job_id: job_id.to_string(),
+            })
+    }
+
+    /// Replay the job's events so far and follow it until it finishes
+    pub fn subscribe(&self, job_id: &str) -> Result<JobSubscription, ServiceError> {
+        let id = parse_job_id(job_id)?;
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(&id).ok_or_else(|| ServiceError::JobNotFound {
+            job_id: job_id.to_string(),
+        })?;
+
+        Ok(JobSubscription {
+            history: job.events.clone(),
+            receiver: if job.status.is_finished() {
+                None
+            } else {
+                Some(job.sender.subscribe())
+            },
+        })
+    }
+
+    /// Abort a queued or running job, killing its cargo process
+    pub fn cancel(&self, job_id: &str) -> Result<JobStatusResponse, ServiceError> {
+        let id = parse_job_id(job_id)?;
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get_mut(&id).ok_or_else(|| ServiceError::JobNotFound {
+            job_id: job_id.to_string(),
+        })?;
+
+        if job.status.is_finished() {
+            return Err(ServiceError::JobAlreadyFinished {
+                job_id: job_id.to_string(),
+                status: job.status.as_str().to_string(),
+            });
+        }
+
+        if let Some(handle) = job.abort_handle.take() {
+            handle.abort();
+        }
+        job.status = JobStatus::Cancelled;
+        job.finished_at = Some(Utc::now());
+        job.queue_ticket = None;
+        job.emit(CompileEvent::Cancelled);
+
+        info!("Compile job {} cancelled", id);
+        Ok(job.to_response(id, &self.queue))
+    }
+
+    fn mark_running(&self, job_id: Uuid) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&job_id) {
+            if job.status == JobStatus::Queued {
+                job.queue_ticket = None;
+                job.status = JobStatus::Running;
+                job.started_at = Some(Utc::now());
+                job.emit(CompileEvent::Status {
+                    status: JobStatus::Running,
+                });
+            }
+        }
+    }
+
+    fn finish(&self, job_id: Uuid, result: anyhow::Result<CompileResponse>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(job) = jobs.get_mut(&job_id) else {
+            return;
+        };
+
+        // A cancelled job keeps its state even if the build raced to completion
+        if job.status.is_finished() {
+            return;
+        }
+
+        match result {
+            Ok(response) => {
+                job.status = JobStatus::Completed;
+                job.result = Some(response.clone());
+                job.emit(CompileEvent::Completed { result: Box::new(response) });
+            }
+            Err(e) => {
+                let (_, api_error) =
+                    to_api_error(&e, "COMPILATION_FAILED", "Failed to compile contract");
+                job.error = Some(api_error.clone());
+                if matches!(
+                    e.downcast_ref::<ServiceError>(),
+                    Some(ServiceError::BuildSuperseded { .. })
+                ) {
+                    job.status = JobStatus::Superseded;
+                    job.emit(CompileEvent::Superseded);
+                } else {
+                    job.status = JobStatus::Failed;
+                    job.emit(CompileEvent::Failed { error: api_error });
+                }
+            }
+        }
+        job.finished_at = Some(Utc::now());
+        job.abort_handle = None;
+        debug!("Compile job {} finished with status {}", job_id, job.status.as_str());
+    }
+}
+
+fn parse_job_id(job_id: &str) -> Result<Uuid, ServiceError> {
+    Uuid::parse_str(job_id).map_err(|_| ServiceError::JobNotFound {
+        job_id: job_id.to_string(),
+    })
+}
+
+/// Drop jobs for the same project that are still waiting for a build slot;
+/// the new request will build the latest sources anyway
+fn supersede_queued(jobs: &mut HashMap<Uuid, Job>, user_id: &str, project_id: &str) {
+    for (job_id, job) in jobs.iter_mut() {
+        if job.status == JobStatus::Queued && job.user_id == user_id && job.project_id == project_id
+        {
+            if let Some(handle) = job.abort_handle.take() {
+                handle.abort();
+            }
+            job.status = JobStatus::Superseded;
+            job.finished_at = Some(Utc::now());
+            job.queue_ticket = None;
+            job.emit(CompileEvent::Superseded);
+            info!("Compile job {} superseded by a newer request", job_id);
+        }
+    }
+}
+
+fn prune_finished(jobs: &mut HashMap<Uuid, Job>) {
+    let cutoff = Utc::now() - Duration::minutes(FINISHED_JOB_TTL_MINUTES);
+    jobs.retain(|_, job| job.finished_at.is_none_or(|finished| finished > cutoff));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::project_locks::ProjectLocks;
+
+    fn registry() -> JobRegistry {
+        JobRegistry::new(BuildQueue::new(1, 10))
+    }
+
+    #[actix_rt::test]
+    async fn test_failed_job_reports_error() {
+        let registry = registry();
+        let locks = ProjectLocks::new();
+        let ticket = registry.queue.enqueue().unwrap();
+        let claim = locks.claim_compile("user", "project");
+        let job = registry.spawn("user", "project", ticket, claim, |events| async move {
+            events.emit(CompileEvent::Progress {
+                message: "Compiling contract v0.1.0".to_string(),
+            });
+            Err(anyhow::anyhow!("cargo exploded"))
+        });
+        assert_eq!(job.status, JobStatus::Queued);
+
+        tokio::task::yield_now().await;
+
+        let job = registry.get(&job.job_id).unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.error.unwrap().code, "COMPILATION_FAILED");
+        assert!(registry.cancel(&job.job_id).is_err());
+
+        let subscription = registry.subscribe(&job.job_id).unwrap();
+        let names: Vec<_> = subscription.history.iter().map(CompileEvent::name).collect();
+        assert_eq!(names, vec!["status", "status", "progress", "failed"]);
+        assert!(subscription.receiver.is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_cancel_running_job() {
+        let registry = registry();
+        let locks = ProjectLocks::new();
+        let ticket = registry.queue.enqueue().unwrap();
+        let claim = locks.claim_compile("user", "project");
+        let job = registry.spawn("user", "project", ticket, claim, |_| async {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            Err(anyhow::anyhow!("should have been cancelled"))
+        });
+        tokio::task::yield_now().await;
+
+        // A second job waits behind the first for the only build slot
+        let ticket = registry.queue.enqueue().unwrap();
+        let claim = locks.claim_compile("user", "other");
+        let superseded = registry.spawn("user", "other", ticket, claim, |_| async {
+            Err(anyhow::anyhow!("should have been superseded"))
+        });
+        assert_eq!(superseded.queue_position, Some(1));
+
+        // A newer request for the same project replaces it in the queue
+        let ticket = registry.queue.enqueue().unwrap();
+        let claim = locks.claim_compile("user", "other");
+        let queued = registry.spawn("user", "other", ticket, claim, |_| async {
+            Err(anyhow::anyhow!("ran after the first job"))
+        });
+        let superseded = registry.get(&superseded.job_id).unwrap();
+        assert_eq!(superseded.status, JobStatus::Superseded);
+        assert_eq!(superseded.queue_position, None);
+
+        tokio::task::yield_now().await;
+        assert_eq!(registry.get(&job.job_id).unwrap().status, JobStatus::Running);
+        let queued = registry.get(&queued.job_id).unwrap();
+        assert_eq!(queued.status, JobStatus::Queued);
+        assert_eq!(queued.queue_position, Some(1));
+
+        let mut receiver = registry.subscribe(&job.job_id).unwrap().receiver.unwrap();
+        let cancelled = registry.cancel(&job.job_id).unwrap();
+        assert_eq!(cancelled.status, JobStatus::Cancelled);
+        assert!(cancelled.finished_at.is_some());
+        assert!(matches!(receiver.recv().await, Ok(CompileEvent::Cancelled)));
+
+        // Cancelling the running job frees the slot for the queued one
+        for _ in 0..3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(registry.get(&queued.job_id).unwrap().status, JobStatus::Failed);
+
+        assert!(matches!(
+            registry.get("not-a-job"),
+            Err(ServiceError::JobNotFound { .. })
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn test_job_waiting_on_its_project_holds_no_build_slot() {
+        let registry = registry();
+        let locks = ProjectLocks::new();
+        let deploy = locks.claim_deploy("user", "busy").lock().await.unwrap();
+
+        let ticket = registry.queue.enqueue().unwrap();
+        let claim = locks.claim_compile("user", "busy");
+        let blocked = registry.spawn("user", "busy", ticket, claim, |_| async {
+            Err(anyhow::anyhow!("ran after the deploy"))
+        });
+        let ticket = registry.queue.enqueue().unwrap();
+        let claim = locks.claim_compile("user", "other");
+        let other = registry.spawn("user", "other", ticket, claim, |_| async {
+            Err(anyhow::anyhow!("ran while the first job waited"))
+        });
+
+        // The only build slot goes to the job that can use it; failing means it ran
+        for _ in 0..3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(registry.get(&blocked.job_id).unwrap().status, JobStatus::Queued);
+        assert_eq!(registry.get(&other.job_id).unwrap().status, JobStatus::Failed);
+
+        drop(deploy);
+        for _ in 0..3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(registry.get(&blocked.job_id).unwrap().status, JobStatus::Failed);
+    }
+}
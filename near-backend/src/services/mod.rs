@@ -1,3 +1,25 @@
+pub mod abi_check;
+pub mod artifacts;
+pub mod auth;
+pub mod build_mode;
+pub mod build_queue;
 pub mod compilation;
+pub mod compile_cache;
+pub mod dependencies;
+pub mod diagnostics;
+pub mod dependency_cache;
 pub mod deployment;
-pub mod method_call;
\ No newline at end of file
+pub mod jobs;
+pub mod method_call;
+pub mod project_locks;
+pub mod sandbox;
+pub mod storage_cost;
+pub mod templates;
+pub mod toolchains;
+pub mod wasm_optimize;
+pub mod wasm_report;
+pub mod process;
+pub mod project_files;
+pub mod projects;
+pub mod rate_limits;
+pub mod reaper;
@@ -0,0 +1,279 @@
+use anyhow::{Context, Result};
+use log::warn;
+use std::cell::RefCell;
+use std::process::{ExitStatus, Stdio};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+
+use crate::errors::ServiceError;
+use crate::models::{CompileEvent, OutputStream};
+
+/// Cargo status verbs reported as progress events
+const PROGRESS_PREFIXES: [&str; 6] = [
+    "Updating",
+    "Downloading",
+    "Downloaded",
+    "Compiling",
+    "Finished",
+    "Building",
+];
+
+/// Bytes of each output stream kept for the response; older output is dropped
+/// first so the final errors survive
+const MAX_COLLECTED_OUTPUT: usize = 1024 * 1024;
+
+/// Collected output of a finished cargo process
+pub struct BuildOutput {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Tail of an output stream, bounded by `MAX_COLLECTED_OUTPUT`
+#[derive(Default)]
+struct OutputTail {
+    text: String,
+    dropped: usize,
+}
+
+impl OutputTail {
+    fn push(&mut self, line: &str) {
+        self.text.push_str(line);
+        if self.text.len() <= MAX_COLLECTED_OUTPUT {
+            return;
+        }
+
+        // Drop whole lines, down to three quarters of the cap so that
+        // trimming is not repeated for every new line
+        let excess = self.text.len() - MAX_COLLECTED_OUTPUT * 3 / 4;
+        let cut = match self.text[excess..].find('\n') {
+            Some(newline) => excess + newline + 1,
+            None => (excess..=self.text.len())
+                .find(|&index| self.text.is_char_boundary(index))
+                .unwrap_or(self.text.len()),
+        };
+        self.text.drain(..cut);
+        self.dropped += cut;
+    }
+
+    fn into_string(self) -> String {
+        if self.dropped == 0 {
+            return self.text;
+        }
+        format!(
+            "[{} bytes of earlier output truncated]\n{}",
+            self.dropped, self.text
+        )
+    }
+}
+
+/// Kills a spawned command's whole process group unless disarmed, so rustc,
+/// build scripts and proc-macro servers do not outlive a timed-out or
+/// cancelled build
+struct ProcessGroup {
+    pgid: Option<u32>,
+}
+
+impl ProcessGroup {
+    fn disarm(&mut self) {
+        self.pgid = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.pgid {
+            // SAFETY: killpg has no memory-safety preconditions
+            if unsafe { libc::killpg(pgid as libc::pid_t, libc::SIGKILL) } != 0 {
+                warn!("Failed to kill process group {}", pgid);
+            }
+        }
+    }
+}
+
+/// Run a command, streaming its output while collecting it. A command still
+/// running at `deadline` is killed together with its children and reported
+/// as `COMPILATION_TIMEOUT` with the output captured so far.
+pub async fn run_command(
+    mut command: Command,
+    deadline: Instant,
+    on_event: &dyn Fn(CompileEvent),
+) -> Result<BuildOutput> {
+    let started = Instant::now();
+
+    // Put cargo in its own process group so the whole tree can be killed
+    #[cfg(unix)]
+    // SAFETY: setpgid is async-signal-safe
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setpgid(0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    // kill_on_drop covers platforms without process groups
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut group = ProcessGroup { pgid: child.id() };
+
+    let stdout = child.stdout.take().context("Failed to capture cargo stdout")?;
+    let stderr = child.stderr.take().context("Failed to capture cargo stderr")?;
+    let collected_stdout = RefCell::new(OutputTail::default());
+    let collected_stderr = RefCell::new(OutputTail::default());
+
+    let run = async {
+        tokio::try_join!(
+            stream_lines(stdout, OutputStream::Stdout, &collected_stdout, on_event),
+            stream_lines(stderr, OutputStream::Stderr, &collected_stderr, on_event),
+            async { child.wait().await.context("Failed to wait for cargo") },
+        )
+    };
+
+    match tokio::time::timeout_at(deadline.into(), run).await {
+        Ok(result) => {
+            let (_, _, status) = result?;
+            group.disarm();
+            Ok(BuildOutput {
+                status,
+                stdout: collected_stdout.into_inner().into_string(),
+                stderr: collected_stderr.into_inner().into_string(),
+            })
+        }
+        Err(_) => {
+            warn!(
+                "Command timed out after {:.0}s, killing its process group",
+                started.elapsed().as_secs_f64()
+            );
+            drop(group);
+            Err(ServiceError::CompilationTimeout {
+                elapsed_secs: started.elapsed().as_secs(),
+                stdout: collected_stdout.into_inner().into_string(),
+                stderr: collected_stderr.into_inner().into_string(),
+            }
+            .into())
+        }
+    }
+}
+
+/// Forward each line of a cargo output pipe as it arrives, appending it to `collected`
+async fn stream_lines<R: AsyncRead + Unpin>(
+    reader: R,
+    stream: OutputStream,
+    collected: &RefCell<OutputTail>,
+    on_event: &dyn Fn(CompileEvent),
+) -> Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+
+    loop {
+        buffer.clear();
+        let read = reader
+            .read_until(b'\n', &mut buffer)
+            .await
+            .context("Failed to read cargo output")?;
+        if read == 0 {
+            break;
+        }
+
+        let line = String::from_utf8_lossy(&buffer);
+        collected.borrow_mut().push(&line);
+
+        let line = line.trim_end().to_string();
+        let trimmed = line.trim_start();
+        if PROGRESS_PREFIXES.iter().any(|prefix| trimmed.starts_with(prefix)) {
+            on_event(CompileEvent::Progress {
+                message: trimmed.to_string(),
+            });
+        }
+        on_event(CompileEvent::Output { stream, line });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[actix_rt::test]
+    async fn test_stream_lines_reports_output_and_progress() {
+        let output: &[u8] = b"    Updating crates.io index\n   Compiling near-sdk v5.5.0\nwarning: unused variable\n";
+        let collected = RefCell::new(OutputTail::default());
+        let events = RefCell::new(Vec::new());
+
+        stream_lines(output, OutputStream::Stderr, &collected, &|event| {
+            events.borrow_mut().push(event)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            collected.into_inner().into_string(),
+            String::from_utf8_lossy(output)
+        );
+        let events = events.into_inner();
+        let names: Vec<_> = events.iter().map(CompileEvent::name).collect();
+        assert_eq!(names, vec!["progress", "output", "progress", "output", "output"]);
+        assert!(matches!(
+            &events[2],
+            CompileEvent::Progress { message } if message == "Compiling near-sdk v5.5.0"
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn test_stream_lines_keeps_tail_of_long_output() {
+        let output: String = (0..200_000).map(|i| format!("line {}\n", i)).collect();
+        let collected = RefCell::new(OutputTail::default());
+
+        stream_lines(output.as_bytes(), OutputStream::Stdout, &collected, &|_| {})
+            .await
+            .unwrap();
+
+        let collected = collected.into_inner().into_string();
+        assert!(collected.len() <= MAX_COLLECTED_OUTPUT + 64);
+        let (marker, tail) = collected.split_once('\n').unwrap();
+        assert!(marker.starts_with('[') && marker.ends_with("bytes of earlier output truncated]"));
+        assert!(tail.starts_with("line "));
+        assert!(output.ends_with(tail));
+        assert!(tail.ends_with("line 199999\n"));
+    }
+
+    #[cfg(unix)]
+    #[actix_rt::test]
+    async fn test_timeout_kills_process_tree_and_keeps_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("child.pid");
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "sleep 30 & echo $! > \"$1\"; echo started; wait", "sh"])
+            .arg(&pid_file);
+
+        let deadline = Instant::now() + Duration::from_millis(500);
+        let error = run_command(command, deadline, &|_| {}).await.err().unwrap();
+
+        match error.downcast_ref::<ServiceError>() {
+            Some(ServiceError::CompilationTimeout { stdout, .. }) => {
+                assert_eq!(stdout, "started\n")
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        // The backgrounded child went with the group; allow a moment for it to be reaped
+        let pid: libc::pid_t = std::fs::read_to_string(&pid_file).unwrap().trim().parse().unwrap();
+        // SAFETY: signal 0 only checks that the process exists
+        let alive = || unsafe { libc::kill(pid, 0) } == 0;
+        let give_up = Instant::now() + Duration::from_secs(5);
+        while alive() && Instant::now() < give_up {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(!alive(), "background child {} survived the timeout", pid);
+    }
+}
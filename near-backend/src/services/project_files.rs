@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use log::debug;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::errors::ServiceError;
+
+/// Maximum number of files accepted in a single request
+const MAX_FILES: usize = 200;
+
+/// Maximum size of a single submitted file (1 MiB)
+const MAX_FILE_SIZE: usize = 1024 * 1024;
+
+/// Top-level paths users cannot write into: build output, version control,
+/// and cargo and rustup configuration, which the server controls
+const RESERVED_PATHS: [&str; 5] = ["target", ".git", ".cargo", "rust-toolchain", "rust-toolchain.toml"];
+
+/// Reserved paths that change how a build runs, removed before every build
+const BUILD_CONFIG_PATHS: [&str; 3] = [".cargo", "rust-toolchain", "rust-toolchain.toml"];
+
+/// Whether a top-level entry of a project is server-managed rather than a source file
+pub fn is_reserved(name: &OsStr) -> bool {
+    RESERVED_PATHS.iter().any(|reserved| name == *reserved)
+}
+
+/// Resolve a user-supplied relative path inside the project root, rejecting
+/// anything that could escape it.
+pub fn resolve_project_file(project_path: &Path, relative: &str) -> Result<PathBuf, ServiceError> {
+    let invalid = |reason: &str| ServiceError::InvalidFilePath {
+        path: relative.to_string(),
+        reason: reason.to_string(),
+    };
+
+    if relative.is_empty() {
+        return Err(invalid("path is empty"));
+    }
+    if relative.contains('\\') || relative.contains('\0') {
+        return Err(invalid("path contains forbidden characters"));
+    }
+
+    let mut resolved = project_path.to_path_buf();
+    let mut depth = 0;
+    for component in Path::new(relative).components() {
+        match component {
+            Component::Normal(part) => {
+                if depth == 0 && is_reserved(part) {
+                    return Err(invalid("path targets a reserved file or directory"));
+                }
+                resolved.push(part);
+                depth += 1;
+            }
+            Component::CurDir => {}
+            Component::ParentDir => return Err(invalid("path must not contain '..'")),
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(invalid("path must be relative to the project root"))
+            }
+        }
+    }
+
+    if depth == 0 {
+        return Err(invalid("path does not name a file"));
+    }
+
+    // Refuse to follow symlinks that already exist inside the project
+    let mut current = project_path.to_path_buf();
+    for component in resolved.strip_prefix(project_path).unwrap_or(&resolved).components() {
+        current.push(component);
+        if let Ok(metadata) = fs::symlink_metadata(&current) {
+            if metadata.file_type().is_symlink() {
+                return Err(invalid("path traverses a symbolic link"));
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Write the submitted file tree into the project and remove deleted files.
+/// Every path is validated before anything touches the disk.
+pub fn apply_file_changes(
+    project_path: &Path,
+    files: &BTreeMap<String, String>,
+    deleted_files: &[String],
+) -> Result<()> {
+    let count = files.len() + deleted_files.len();
+    if count > MAX_FILES {
+        return Err(ServiceError::TooManyFiles { count, max: MAX_FILES }.into());
+    }
+
+    let mut writes = Vec::with_capacity(files.len());
+    for (relative, contents) in files {
+        if contents.len() > MAX_FILE_SIZE {
+            return Err(ServiceError::InvalidFilePath {
+                path: relative.clone(),
+                reason: format!("file exceeds {} bytes", MAX_FILE_SIZE),
+            }
+            .into());
+        }
+        writes.push((resolve_project_file(project_path, relative)?, contents));
+    }
+
+    let mut deletions = Vec::with_capacity(deleted_files.len());
+    for relative in deleted_files {
+        deletions.push(resolve_project_file(project_path, relative)?);
+    }
+
+    for path in deletions {
+        if path.is_file() {
+            fs::remove_file(&path).with_context(|| format!("Failed to delete file: {:?}", path))?;
+            debug!("Deleted project file: {:?}", path);
+        }
+    }
+
+    for (path, contents) in writes {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        fs::write(&path, contents).with_context(|| format!("Failed to write file: {:?}", path))?;
+        debug!("Wrote project file: {:?}", path);
+    }
+
+    Ok(())
+}
+
+/// Delete cargo configuration and toolchain overrides from a project, such
+/// as ones written before they were reserved, so only the server's settings
+/// apply to the build
+pub fn remove_build_config(project_path: &Path) -> Result<()> {
+    for name in BUILD_CONFIG_PATHS {
+        let path = project_path.join(name);
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let removed = if metadata.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        removed.with_context(|| format!("Failed to remove {:?}", path))?;
+        debug!("Removed build configuration {:?}", path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_project_file() {
+        let root = Path::new("projects/user/project");
+
+        assert_eq!(
+            resolve_project_file(root, "src/lib.rs").unwrap(),
+            root.join("src").join("lib.rs")
+        );
+        assert_eq!(
+            resolve_project_file(root, "./src/storage.rs").unwrap(),
+            root.join("src").join("storage.rs")
+        );
+        assert!(resolve_project_file(root, "Cargo.toml").is_ok());
+
+        assert!(resolve_project_file(root, "").is_err());
+        assert!(resolve_project_file(root, ".").is_err());
+        assert!(resolve_project_file(root, "../other/src/lib.rs").is_err());
+        assert!(resolve_project_file(root, "src/../../lib.rs").is_err());
+        assert!(resolve_project_file(root, "/etc/passwd").is_err());
+        assert!(resolve_project_file(root, "src\\..\\lib.rs").is_err());
+        assert!(resolve_project_file(root, "target/near/contract.wasm").is_err());
+        assert!(resolve_project_file(root, ".git/config").is_err());
+        assert!(resolve_project_file(root, ".cargo/config.toml").is_err());
+        assert!(resolve_project_file(root, "rust-toolchain.toml").is_err());
+        assert!(resolve_project_file(root, "src/rust-toolchain.toml").is_ok());
+    }
+
+    #[test]
+    fn test_apply_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src").join("old.rs"), "// old").unwrap();
+
+        let mut files = BTreeMap::new();
+        files.insert("src/lib.rs".to_string(), "mod storage;".to_string());
+        files.insert("src/storage.rs".to_string(), "// storage".to_string());
+        apply_file_changes(root, &files, &["src/old.rs".to_string()]).unwrap();
+
+        assert_eq!(fs::read_to_string(root.join("src/lib.rs")).unwrap(), "mod storage;");
+        assert_eq!(fs::read_to_string(root.join("src/storage.rs")).unwrap(), "// storage");
+        assert!(!root.join("src/old.rs").exists());
+
+        // A single bad path rejects the whole change set
+        let mut files = BTreeMap::new();
+        files.insert("src/lib.rs".to_string(), "changed".to_string());
+        files.insert("../escape.rs".to_string(), "".to_string());
+        assert!(apply_file_changes(root, &files, &[]).is_err());
+        assert_eq!(fs::read_to_string(root.join("src/lib.rs")).unwrap(), "mod storage;");
+
+        let files: BTreeMap<_, _> = (0..=MAX_FILES).map(|i| (format!("src/{}.rs", i), String::new())).collect();
+        let error = apply_file_changes(root, &files, &[]).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(ServiceError::TooManyFiles { .. })));
+    }
+
+    #[test]
+    fn test_remove_build_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".cargo")).unwrap();
+        fs::write(root.join(".cargo/config.toml"), "[build]\nrustflags = []").unwrap();
+        fs::write(root.join("rust-toolchain"), "nightly").unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+
+        remove_build_config(root).unwrap();
+        assert!(!root.join(".cargo").exists());
+        assert!(!root.join("rust-toolchain").exists());
+        assert!(root.join("Cargo.toml").exists());
+    }
+}
@@ -0,0 +1,228 @@
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::{Notify, OwnedMutexGuard};
+
+use crate::errors::ServiceError;
+
+struct ProjectEntry {
+    lock: Arc<tokio::sync::Mutex<()>>,
+    /// Generation of the most recent compile request for the project
+    latest_compile: u64,
+    /// Wakes waiting compiles when a newer one arrives
+    superseded: Arc<Notify>,
+    /// Outstanding claims; the entry is dropped when this reaches zero
+    claims: usize,
+}
+
+/// Serializes work on a project directory so compiles and deploys of the
+/// same project never overlap. A newer compile request supersedes older
+/// ones that have not started yet.
+#[derive(Clone, Default)]
+pub struct ProjectLocks {
+    entries: Arc<Mutex<HashMap<String, ProjectEntry>>>,
+    /// When each project was last locked by a request, for garbage collection
+    accessed: Arc<Mutex<HashMap<String, SystemTime>>>,
+}
+
+/// Interest in a project, registered when the request arrives
+pub struct ProjectClaim {
+    locks: ProjectLocks,
+    key: String,
+    /// `Some` for compiles, which can be superseded; `None` for deploys
+    generation: Option<u64>,
+    /// Whether taking the lock counts as use of the project
+    records_access: bool,
+}
+
+/// Exclusive access to a project directory, released on drop
+pub struct ProjectGuard {
+    _guard: OwnedMutexGuard<()>,
+    _claim: ProjectClaim,
+}
+
+impl ProjectLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a compile request, superseding any compile registered before
+    /// it that has not yet taken the lock
+    pub fn claim_compile(&self, user_id: &str, project_id: &str) -> ProjectClaim {
+        self.claim(user_id, project_id, true)
+    }
+
+    /// Register a deploy request; deploys wait their turn but are never superseded
+    pub fn claim_deploy(&self, user_id: &str, project_id: &str) -> ProjectClaim {
+        self.claim(user_id, project_id, false)
+    }
+
+    /// Register housekeeping on a project; like a deploy, but it does not
+    /// count as use of the project
+    pub fn claim_maintenance(&self, user_id: &str, project_id: &str) -> ProjectClaim {
+        let mut claim = self.claim(user_id, project_id, false);
+        claim.records_access = false;
+        claim
+    }
+
+    /// When a request last locked the project since the server started
+    pub fn last_access(&self, user_id: &str, project_id: &str) -> Option<SystemTime> {
+        let key = format!("{}/{}", user_id, project_id);
+        self.accessed.lock().unwrap().get(&key).copied()
+    }
+
+    /// Forget a project's access time once its directory is gone
+    pub fn forget(&self, user_id: &str, project_id: &str) {
+        let key = format!("{}/{}", user_id, project_id);
+        self.accessed.lock().unwrap().remove(&key);
+    }
+
+    fn claim(&self, user_id: &str, project_id: &str, compile: bool) -> ProjectClaim {
+        let key = format!("{}/{}", user_id, project_id);
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(key.clone()).or_insert_with(|| ProjectEntry {
+            lock: Arc::new(tokio::sync::Mutex::new(())),
+            latest_compile: 0,
+            superseded: Arc::new(Notify::new()),
+            claims: 0,
+        });
+        entry.claims += 1;
+
+        let generation = if compile {
+            entry.latest_compile += 1;
+            entry.superseded.notify_waiters();
+            Some(entry.latest_compile)
+        } else {
+            None
+        };
+
+        ProjectClaim {
+            locks: self.clone(),
+            key,
+            generation,
+            records_access: true,
+        }
+    }
+}
+
+impl ProjectClaim {
+    /// Wait for exclusive access to the project. A compile fails as soon as
+    /// a newer compile request arrives, without waiting for the lock.
+    pub async fn lock(self) -> Result<ProjectGuard, ServiceError> {
+        let (lock, superseded) = {
+            let entries = self.locks.entries.lock().unwrap();
+            let entry = &entries[&self.key];
+            (entry.lock.clone(), entry.superseded.clone())
+        };
+
+        let guard = if self.generation.is_some() {
+            let notified = superseded.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.is_superseded() {
+                return Err(self.superseded_error());
+            }
+            tokio::select! {
+                guard = lock.lock_owned() => guard,
+                _ = notified => return Err(self.superseded_error()),
+            }
+        } else {
+            lock.lock_owned().await
+        };
+
+        if self.is_superseded() {
+            return Err(self.superseded_error());
+        }
+
+        if self.records_access {
+            let mut accessed = self.locks.accessed.lock().unwrap();
+            accessed.insert(self.key.clone(), SystemTime::now());
+        }
+        debug!("Acquired project lock for {}", self.key);
+        Ok(ProjectGuard {
+            _guard: guard,
+            _claim: self,
+        })
+    }
+
+    fn superseded_error(&self) -> ServiceError {
+        info!("Build of {} superseded by a newer request", self.key);
+        ServiceError::BuildSuperseded {
+            project: self.key.clone(),
+        }
+    }
+
+    fn is_superseded(&self) -> bool {
+        let Some(generation) = self.generation else {
+            return false;
+        };
+        let entries = self.locks.entries.lock().unwrap();
+        entries[&self.key].latest_compile != generation
+    }
+}
+
+impl Drop for ProjectClaim {
+    fn drop(&mut self) {
+        let mut entries = self.locks.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(&self.key) {
+            entry.claims -= 1;
+            if entry.claims == 0 {
+                entries.remove(&self.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn test_newer_compile_supersedes_waiting_one() {
+        let locks = ProjectLocks::new();
+
+        let running = locks.claim_compile("user", "project").lock().await.unwrap();
+        let waiting = locks.claim_compile("user", "project");
+        let deploy = locks.claim_deploy("user", "project");
+        let newest = locks.claim_compile("user", "project");
+
+        // Other projects are unaffected
+        let other = locks.claim_compile("user", "other").lock().await;
+        assert!(other.is_ok());
+
+        drop(running);
+        assert!(matches!(
+            waiting.lock().await,
+            Err(ServiceError::BuildSuperseded { .. })
+        ));
+
+        let deploy = deploy.lock().await.unwrap();
+        drop(deploy);
+        let newest = newest.lock().await;
+        assert!(newest.is_ok());
+        drop(newest);
+        drop(other);
+
+        assert!(locks.entries.lock().unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_superseded_compile_stops_waiting() {
+        let locks = ProjectLocks::new();
+
+        let running = locks.claim_compile("user", "project").lock().await.unwrap();
+        let waiting = actix_rt::spawn(locks.claim_compile("user", "project").lock());
+        tokio::task::yield_now().await;
+        let _newest = locks.claim_compile("user", "project");
+
+        // Gives up while the running build still holds the lock, so it
+        // leaves the build queue without waiting its turn
+        let waited = tokio::time::timeout(std::time::Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(waited, Err(ServiceError::BuildSuperseded { .. })));
+        drop(running);
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::ServiceError;
+use crate::models::{BuildMode, CompileResponse, LastBuild, ProjectDetails, ProjectId, ProjectSummary, UserId};
+use crate::services::artifacts::{newest_source, project_dir, user_dir};
+use crate::services::compilation::copy_project;
+use crate::services::project_files::is_reserved;
+
+/// Where a project remembers its last build. Under `target` so it never
+/// counts as a source and is removed along with the build output; the
+/// dependency cache treats it as project-specific.
+const BUILD_RECORD_DIR: &str = "target/nearplay";
+const BUILD_RECORD_FILE: &str = "last_build.json";
+
+/// Remember the outcome of a compile for project listings
+pub fn record_build(project_path: &Path, build_mode: BuildMode, response: &CompileResponse) -> Result<()> {
+    let record = LastBuild {
+        success: response.success,
+        finished_at: Utc::now(),
+        compilation_time: response.details.compilation_time,
+        build_mode,
+        wasm_size: response.details.wasm_size,
+        code_hash: response.details.code_hash.clone(),
+        cache_hit: response.cache_hit,
+    };
+    let dir = project_path.join(BUILD_RECORD_DIR);
+    fs::create_dir_all(&dir).context("Failed to create build record directory")?;
+    fs::write(dir.join(BUILD_RECORD_FILE), serde_json::to_vec(&record)?)
+        .context("Failed to write build record")?;
+    Ok(())
+}
+
+/// A user's projects, most recently edited first
+pub fn list_projects(user_id: &UserId) -> Result<Vec<ProjectSummary>> {
+    let dir = user_dir(user_id);
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+
+    let mut projects: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| summary(&entry.file_name().to_string_lossy(), &entry.path()))
+        .collect();
+    projects.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(a.project_id.cmp(&b.project_id)));
+    Ok(projects)
+}
+
+/// A project's summary and its current source files. Files that are not
+/// UTF-8 text are left out.
+pub fn project_details(user_id: &UserId, project_id: &ProjectId) -> Result<ProjectDetails> {
+    let project_path = existing_project(user_id, project_id)?;
+    let mut files = BTreeMap::new();
+    read_sources(&project_path, Path::new(""), &mut files)
+        .with_context(|| format!("Failed to read sources of {:?}", project_path))?;
+
+    Ok(ProjectDetails {
+        project: summary(project_id, &project_path),
+        files,
+    })
+}
+
+/// Copy a project's sources to a new id; the copy builds from scratch
+pub fn fork_project(user_id: &UserId, project_id: &ProjectId, new_project_id: &ProjectId) -> Result<ProjectSummary> {
+    let source = existing_project(user_id, project_id)?;
+    let target = free_project(user_id, new_project_id)?;
+    copy_project(&source, &target)?;
+    Ok(summary(new_project_id, &target))
+}
+
+/// Move a project, with its build output, to a new id
+pub fn rename_project(user_id: &UserId, project_id: &ProjectId, new_project_id: &ProjectId) -> Result<ProjectSummary> {
+    let source = existing_project(user_id, project_id)?;
+    let target = free_project(user_id, new_project_id)?;
+    fs::rename(&source, &target).with_context(|| format!("Failed to rename {:?}", source))?;
+    Ok(summary(new_project_id, &target))
+}
+
+/// Remove a project's sources and build artifacts
+pub fn delete_project(user_id: &UserId, project_id: &ProjectId) -> Result<()> {
+    let project_path = existing_project(user_id, project_id)?;
+    fs::remove_dir_all(&project_path).with_context(|| format!("Failed to delete {:?}", project_path))?;
+    Ok(())
+}
+
+fn existing_project(user_id: &UserId, project_id: &ProjectId) -> Result<PathBuf, ServiceError> {
+    let project_path = project_dir(user_id, project_id);
+    if !project_path.is_dir() {
+        return Err(ServiceError::ProjectNotFound {
+            project: format!("{}/{}", user_id, project_id),
+        });
+    }
+    Ok(project_path)
+}
+
+fn free_project(user_id: &UserId, project_id: &ProjectId) -> Result<PathBuf, ServiceError> {
+    let project_path = project_dir(user_id, project_id);
+    if project_path.exists() {
+        return Err(ServiceError::ProjectExists {
+            project: format!("{}/{}", user_id, project_id),
+        });
+    }
+    Ok(project_path)
+}
+
+fn summary(project_id: &str, project_path: &Path) -> ProjectSummary {
+    let last_build = fs::read(project_path.join(BUILD_RECORD_DIR).join(BUILD_RECORD_FILE))
+        .ok()
+        .and_then(|record| serde_json::from_slice(&record).ok());
+
+    ProjectSummary {
+        project_id: project_id.to_string(),
+        updated_at: newest_source(project_path, Path::new("")).map(DateTime::<Utc>::from),
+        last_build,
+    }
+}
+
+fn read_sources(root: &Path, relative: &Path, files: &mut BTreeMap<String, String>) -> Result<()> {
+    for entry in fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if relative.as_os_str().is_empty() && is_reserved(&name) {
+            continue;
+        }
+
+        let path = relative.join(&name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            read_sources(root, &path, files)?;
+        } else if file_type.is_file() {
+            if let Ok(contents) = fs::read_to_string(entry.path()) {
+                files.insert(path.to_string_lossy().replace('\\', "/"), contents);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_sources_skips_build_output() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::create_dir_all(dir.path().join(BUILD_RECORD_DIR)).unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        fs::write(dir.path().join("rust-toolchain.toml"), "[toolchain]").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "pub fn a() {}").unwrap();
+        fs::write(dir.path().join("src/blob.bin"), [0xff, 0xfe]).unwrap();
+        fs::write(
+            dir.path().join(BUILD_RECORD_DIR).join(BUILD_RECORD_FILE),
+            r#"{"success":true,"finished_at":"2026-01-01T00:00:00Z","compilation_time":1.5,
+                "build_mode":"non-reproducible-wasm","wasm_size":4,"code_hash":null,"cache_hit":false}"#,
+        )
+        .unwrap();
+
+        let mut files = BTreeMap::new();
+        read_sources(dir.path(), Path::new(""), &mut files).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), vec!["Cargo.toml", "src/lib.rs"]);
+
+        let summary = summary("counter", dir.path());
+        assert!(summary.updated_at.is_some());
+        assert_eq!(summary.last_build.map(|build| build.wasm_size), Some(Some(4)));
+    }
+}
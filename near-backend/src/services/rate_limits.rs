@@ -0,0 +1,484 @@
+use actix_web::HttpRequest;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::errors::ServiceError;
+use crate::models::UserId;
+use crate::services::storage_cost::{format_near, parse_near, YOCTO_PER_NEAR};
+
+const DEFAULT_STATE_FILE: &str = "rate_limits.json";
+
+/// How often changed counters are written to the state file
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Something a caller does that costs the operator build time or NEAR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitedAction {
+    Compile,
+    Deploy,
+    /// A change method call signed with the operator's key
+    Call,
+}
+
+impl LimitedAction {
+    fn name(&self) -> &'static str {
+        match self {
+            LimitedAction::Compile => "compile",
+            LimitedAction::Deploy => "deploy",
+            LimitedAction::Call => "call",
+        }
+    }
+
+    fn window(&self) -> Duration {
+        match self {
+            LimitedAction::Compile | LimitedAction::Call => Duration::hours(1),
+            LimitedAction::Deploy => Duration::days(1),
+        }
+    }
+
+    fn window_name(&self) -> &'static str {
+        match self {
+            LimitedAction::Compile | LimitedAction::Call => "hour",
+            LimitedAction::Deploy => "day",
+        }
+    }
+}
+
+/// Allowance for one caller per window; 0 means unlimited
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Quota<T> {
+    pub per_user: T,
+    pub per_ip: T,
+}
+
+/// Counters for one action and caller since `started_at`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Window {
+    started_at: DateTime<Utc>,
+    count: u64,
+    /// yoctoNEAR funded by deployments in this window
+    #[serde(default)]
+    funded: u128,
+}
+
+/// Per-user and per-IP limits on builds, deployments, change calls and the
+/// NEAR deployments fund. Counters use fixed windows and are flushed to disk
+/// every few seconds by [`run`](Self::run) so a restart does not reset them.
+#[derive(Clone)]
+pub struct RateLimiter {
+    compiles: Quota<u64>,
+    deploys: Quota<u64>,
+    calls: Quota<u64>,
+    /// yoctoNEAR deployments may fund per day
+    funding: Quota<u128>,
+    /// Take the client address from `Forwarded`/`X-Forwarded-For`
+    trust_proxy: bool,
+    state_file: Option<PathBuf>,
+    windows: Arc<Mutex<HashMap<String, Window>>>,
+    /// Counters changed since the last flush
+    dirty: Arc<AtomicBool>,
+}
+
+impl RateLimiter {
+    /// Read `COMPILES_PER_HOUR_PER_{USER,IP}`, `DEPLOYS_PER_DAY_PER_{USER,IP}`,
+    /// `CALLS_PER_HOUR_PER_{USER,IP}`, `NEAR_FUNDED_PER_DAY_PER_{USER,IP}` (in
+    /// NEAR), `TRUST_PROXY_HEADERS` and `RATE_LIMITS_FILE`; 0 lifts a limit.
+    /// A limit that is set but malformed is an error, checked at startup.
+    pub fn from_env() -> Result<Self> {
+        let count = |name: &str, default: u64| match env::var(name) {
+            Ok(value) => value
+                .trim()
+                .parse::<u64>()
+                .with_context(|| format!("{} must be a whole number, got '{}'", name, value)),
+            Err(_) => Ok(default),
+        };
+        let near = |name: &str, default: u128| match env::var(name) {
+            Ok(value) => parse_near(&value)
+                .with_context(|| format!("{} must be an amount in NEAR, got '{}'", name, value)),
+            Err(_) => Ok(default),
+        };
+        let state_file = PathBuf::from(env::var("RATE_LIMITS_FILE").unwrap_or_else(|_| DEFAULT_STATE_FILE.to_string()));
+
+        Ok(Self {
+            compiles: Quota {
+                per_user: count("COMPILES_PER_HOUR_PER_USER", 60)?,
+                per_ip: count("COMPILES_PER_HOUR_PER_IP", 120)?,
+            },
+            deploys: Quota {
+                per_user: count("DEPLOYS_PER_DAY_PER_USER", 10)?,
+                per_ip: count("DEPLOYS_PER_DAY_PER_IP", 20)?,
+            },
+            calls: Quota {
+                per_user: count("CALLS_PER_HOUR_PER_USER", 120)?,
+                per_ip: count("CALLS_PER_HOUR_PER_IP", 240)?,
+            },
+            funding: Quota {
+                per_user: near("NEAR_FUNDED_PER_DAY_PER_USER", 5 * YOCTO_PER_NEAR)?,
+                per_ip: near("NEAR_FUNDED_PER_DAY_PER_IP", 10 * YOCTO_PER_NEAR)?,
+            },
+            trust_proxy: env::var("TRUST_PROXY_HEADERS").is_ok_and(|value| value == "true"),
+            windows: Arc::new(Mutex::new(load(&state_file))),
+            state_file: Some(state_file),
+            dirty: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Flush changed counters every `FLUSH_INTERVAL`, forever
+    pub async fn run(self) {
+        let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            self.flush().await;
+        }
+    }
+
+    /// Drop ended windows and, if anything changed, write the rest to the
+    /// state file off the async runtime
+    pub async fn flush(&self) {
+        let Some(path) = self.state_file.clone() else {
+            return;
+        };
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let windows = {
+            let mut windows = self.windows.lock().unwrap();
+            prune(&mut windows, Utc::now());
+            windows.clone()
+        };
+
+        let written = tokio::task::spawn_blocking(move || {
+            let contents = serde_json::to_vec(&windows)?;
+            // Write a sibling file and rename it so a crash never leaves half a file
+            let temp = path.with_extension("json.tmp");
+            fs::write(&temp, contents)?;
+            fs::rename(&temp, &path)
+        })
+        .await;
+        match written {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                warn!("Failed to save rate limit counters: {}", e);
+                self.dirty.store(true, Ordering::Release);
+            }
+            Err(e) => warn!("Rate limit flush task failed: {}", e),
+        }
+    }
+
+    /// The address limits are counted against
+    pub fn client_ip(&self, req: &HttpRequest) -> Option<String> {
+        if !self.trust_proxy {
+            return req.peer_addr().map(|addr| addr.ip().to_string());
+        }
+        let info = req.connection_info();
+        let addr = info.realip_remote_addr()?;
+        Some(match addr.parse::<SocketAddr>() {
+            Ok(socket) => socket.ip().to_string(),
+            Err(_) => addr
+                .parse::<IpAddr>()
+                .map(|ip| ip.to_string())
+                .unwrap_or_else(|_| addr.to_string()),
+        })
+    }
+
+    /// Count one `action` against the user and IP, or refuse it with the
+    /// first limit either has reached. Refused attempts are not counted.
+    pub fn acquire(
+        &self,
+        action: LimitedAction,
+        user: Option<&UserId>,
+        ip: Option<&str>,
+    ) -> Result<(), ServiceError> {
+        self.count(action, user, ip, 0)
+    }
+
+    /// Count a deployment and reserve the yoctoNEAR it will fund against the
+    /// daily funding limits, refusing it if the total would exceed them.
+    /// Reserving under the same lock as the check keeps concurrent deploys
+    /// from overspending together.
+    pub fn reserve_deploy(
+        &self,
+        user: Option<&UserId>,
+        ip: Option<&str>,
+        funding: u128,
+    ) -> Result<FundingReservation, ServiceError> {
+        self.count(LimitedAction::Deploy, user, ip, funding)?;
+        Ok(FundingReservation {
+            limiter: self.clone(),
+            user: user.cloned(),
+            ip: ip.map(str::to_string),
+            amount: funding,
+            reserved_at: Utc::now(),
+            spent: false,
+        })
+    }
+
+    fn count(
+        &self,
+        action: LimitedAction,
+        user: Option<&UserId>,
+        ip: Option<&str>,
+        funding: u128,
+    ) -> Result<(), ServiceError> {
+        let quota = match action {
+            LimitedAction::Compile => self.compiles,
+            LimitedAction::Deploy => self.deploys,
+            LimitedAction::Call => self.calls,
+        };
+        let subjects = subjects(user, ip);
+        let now = Utc::now();
+        let mut windows = self.windows.lock().unwrap();
+
+        for (kind, id) in &subjects {
+            let (limit, funding_limit) = match *kind {
+                "user" => (quota.per_user, self.funding.per_user),
+                _ => (quota.per_ip, self.funding.per_ip),
+            };
+            let Some(window) = current(&mut windows, action, kind, id, now) else {
+                continue;
+            };
+            let reset_at = window.started_at + action.window();
+
+            if limit > 0 && window.count >= limit {
+                return Err(ServiceError::RateLimited {
+                    limit: format!("{} {}s per {} per {}", limit, action.name(), action.window_name(), kind),
+                    reset_at,
+                });
+            }
+            let funded = window.funded.saturating_add(funding);
+            if action == LimitedAction::Deploy && funding_limit > 0 && funded > funding_limit {
+                return Err(ServiceError::RateLimited {
+                    limit: format!("{} NEAR funded per day per {}", format_near(funding_limit), kind),
+                    reset_at,
+                });
+            }
+        }
+
+        for (kind, id) in &subjects {
+            let window = windows.entry(key(action, kind, id)).or_insert_with(|| Window {
+                started_at: now,
+                count: 0,
+                funded: 0,
+            });
+            window.count += 1;
+            window.funded = window.funded.saturating_add(funding);
+        }
+        self.dirty.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Return funding reserved at `reserved_at` that was never sent, unless
+    /// its window has since ended
+    fn release_funding(&self, user: Option<&UserId>, ip: Option<&str>, amount: u128, reserved_at: DateTime<Utc>) {
+        let mut windows = self.windows.lock().unwrap();
+        for (kind, id) in subjects(user, ip) {
+            if let Some(window) = windows.get_mut(&key(LimitedAction::Deploy, kind, &id)) {
+                if window.started_at <= reserved_at {
+                    window.funded = window.funded.saturating_sub(amount);
+                }
+            }
+        }
+        self.dirty.store(true, Ordering::Release);
+    }
+}
+
+/// Funding held for a deployment in progress. Dropping it gives the amount
+/// back unless [`spend`](Self::spend) recorded that the funds were sent.
+#[must_use]
+pub struct FundingReservation {
+    limiter: RateLimiter,
+    user: Option<UserId>,
+    ip: Option<String>,
+    amount: u128,
+    reserved_at: DateTime<Utc>,
+    spent: bool,
+}
+
+impl FundingReservation {
+    /// Keep the reservation: the funding transaction has been broadcast
+    pub fn spend(&mut self) {
+        self.spent = true;
+    }
+}
+
+impl Drop for FundingReservation {
+    fn drop(&mut self) {
+        if !self.spent {
+            self.limiter
+                .release_funding(self.user.as_ref(), self.ip.as_deref(), self.amount, self.reserved_at);
+        }
+    }
+}
+
+fn subjects(user: Option<&UserId>, ip: Option<&str>) -> Vec<(&'static str, String)> {
+    user.map(|user| ("user", user.to_string()))
+        .into_iter()
+        .chain(ip.map(|ip| ("ip", ip.to_string())))
+        .collect()
+}
+
+fn key(action: LimitedAction, kind: &str, id: &str) -> String {
+    format!("{}:{}:{}", action.name(), kind, id)
+}
+
+/// The caller's window for `action`, dropping it once it has ended
+fn current<'a>(
+    windows: &'a mut HashMap<String, Window>,
+    action: LimitedAction,
+    kind: &str,
+    id: &str,
+    now: DateTime<Utc>,
+) -> Option<&'a Window> {
+    let key = key(action, kind, id);
+    if windows
+        .get(&key)
+        .is_some_and(|window| window.started_at + action.window() <= now)
+    {
+        windows.remove(&key);
+    }
+    windows.get(&key)
+}
+
+/// Counters saved by a previous run, without windows that have since ended
+fn load(path: &Path) -> HashMap<String, Window> {
+    let Ok(contents) = fs::read(path) else {
+        return HashMap::new();
+    };
+    let mut windows: HashMap<String, Window> = match serde_json::from_slice(&contents) {
+        Ok(windows) => windows,
+        Err(e) => {
+            warn!("Ignoring unreadable rate limit counters in {:?}: {}", path, e);
+            return HashMap::new();
+        }
+    };
+    prune(&mut windows, Utc::now());
+    windows
+}
+
+/// Drop windows that have ended, which keeps the map to recent callers
+fn prune(windows: &mut HashMap<String, Window>, now: DateTime<Utc>) {
+    windows.retain(|key, window| {
+        let window_length = if key.starts_with("deploy:") {
+            LimitedAction::Deploy.window()
+        } else {
+            LimitedAction::Compile.window()
+        };
+        window.started_at + window_length > now
+    });
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn limiter(state_file: Option<PathBuf>) -> RateLimiter {
+        RateLimiter {
+            compiles: Quota { per_user: 2, per_ip: 3 },
+            deploys: Quota { per_user: 5, per_ip: 0 },
+            calls: Quota::default(),
+            funding: Quota {
+                per_user: parse_near("1").unwrap(),
+                per_ip: 0,
+            },
+            trust_proxy: false,
+            windows: Arc::new(Mutex::new(state_file.as_deref().map(load).unwrap_or_default())),
+            state_file,
+            dirty: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn user(id: &str) -> UserId {
+        UserId::try_from(id.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_limits_per_user_and_ip() {
+        let limiter = limiter(None);
+        let (alice, bob) = (user("alice"), user("bob"));
+
+        limiter.acquire(LimitedAction::Compile, Some(&alice), Some("10.0.0.1")).unwrap();
+        limiter.acquire(LimitedAction::Compile, Some(&alice), Some("10.0.0.1")).unwrap();
+        let refused = limiter.acquire(LimitedAction::Compile, Some(&alice), Some("10.0.0.2"));
+        assert!(matches!(refused, Err(ServiceError::RateLimited { .. })));
+
+        // The IP allowance is shared by every user behind it
+        limiter.acquire(LimitedAction::Compile, Some(&bob), Some("10.0.0.1")).unwrap();
+        let refused = limiter
+            .acquire(LimitedAction::Compile, Some(&bob), Some("10.0.0.1"))
+            .unwrap_err();
+        assert_eq!(refused.to_string().split(';').next(), Some("Rate limit of 3 compiles per hour per ip reached"));
+        assert!(refused.retry_after_secs().is_some_and(|secs| secs <= 3600));
+
+        // Unlimited actions are never refused
+        for _ in 0..10 {
+            limiter.acquire(LimitedAction::Call, Some(&alice), None).unwrap();
+        }
+
+        // A window that has ended starts over
+        limiter.windows.lock().unwrap().values_mut().for_each(|window| {
+            window.started_at -= Duration::hours(2);
+        });
+        limiter.acquire(LimitedAction::Compile, Some(&alice), Some("10.0.0.1")).unwrap();
+    }
+
+    #[test]
+    fn test_funding_is_reserved_before_deploying() {
+        let limiter = limiter(None);
+        let (alice, ip) = (user("alice"), Some("10.0.0.1"));
+        let funding = parse_near("0.6").unwrap();
+
+        // Two deploys in flight together cannot exceed the limit
+        let mut first = limiter.reserve_deploy(Some(&alice), ip, funding).unwrap();
+        let refused = limiter.reserve_deploy(Some(&alice), ip, funding).err().unwrap();
+        assert!(refused.to_string().contains("1 NEAR funded per day per user"), "{}", refused);
+
+        // Funding that was never sent is given back, sent funding is kept
+        drop(limiter.reserve_deploy(Some(&user("bob")), ip, funding).unwrap());
+        first.spend();
+        drop(first);
+        let windows = limiter.windows.lock().unwrap();
+        assert_eq!(windows[&key(LimitedAction::Deploy, "user", "alice")].funded, funding);
+        assert_eq!(windows[&key(LimitedAction::Deploy, "user", "bob")].funded, 0);
+    }
+
+    #[actix_rt::test]
+    async fn test_funding_quota_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("rate_limits.json");
+        let alice = user("alice");
+
+        let first = limiter(Some(state_file.clone()));
+        first
+            .reserve_deploy(Some(&alice), Some("10.0.0.1"), parse_near("0.7").unwrap())
+            .unwrap()
+            .spend();
+        // Ended windows are not kept
+        first.acquire(LimitedAction::Compile, Some(&alice), None).unwrap();
+        if let Some(window) = first.windows.lock().unwrap().get_mut("compile:user:alice") {
+            window.started_at -= Duration::hours(2);
+        }
+        first.flush().await;
+        let saved: HashMap<String, Window> = serde_json::from_slice(&fs::read(&state_file).unwrap()).unwrap();
+        assert!(!saved.contains_key("compile:user:alice"));
+
+        let restarted = limiter(Some(state_file));
+        let refused = restarted
+            .reserve_deploy(Some(&alice), Some("10.0.0.1"), parse_near("0.5").unwrap())
+            .err()
+            .unwrap();
+        assert!(refused.to_string().contains("1 NEAR funded per day per user"), "{}", refused);
+        restarted
+            .reserve_deploy(Some(&user("bob")), Some("10.0.0.1"), parse_near("0.5").unwrap())
+            .unwrap()
+            .spend();
+    }
+}
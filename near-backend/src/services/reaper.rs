@@ -0,0 +1,320 @@
+use chrono::Utc;
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::models::{ReaperStats, UserDiskUsage};
+use crate::services::artifacts::newest_source;
+use crate::services::project_locks::ProjectLocks;
+use crate::services::sandbox::dir_size;
+
+const PROJECTS_DIR: &str = "projects";
+const DEFAULT_INTERVAL_SECS: u64 = 600;
+const DEFAULT_TARGET_MAX_AGE_HOURS: u64 = 72;
+
+/// Periodically frees disk held by idle projects: their `target` directories
+/// after `target_max_age` or once `projects/` exceeds its quota, and whole
+/// projects after `project_max_age`
+#[derive(Clone)]
+pub struct Reaper {
+    root: PathBuf,
+    interval: Duration,
+    target_max_age: Duration,
+    /// `None` keeps idle project sources forever
+    project_max_age: Option<Duration>,
+    /// `None` disables quota-based eviction
+    quota_bytes: Option<u64>,
+    stats: Arc<Mutex<ReaperStats>>,
+}
+
+/// A project as seen by one scan
+struct ProjectUsage {
+    user_id: String,
+    project_id: String,
+    path: PathBuf,
+    target_bytes: u64,
+    total_bytes: u64,
+    last_access: SystemTime,
+}
+
+impl Reaper {
+    /// Read `REAPER_INTERVAL_SECS`, `TARGET_MAX_AGE_HOURS`, `PROJECT_MAX_AGE_DAYS`
+    /// and `PROJECTS_DISK_QUOTA_MB`; 0 disables the last two
+    pub fn from_env() -> Self {
+        let number = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let project_max_age_days = number("PROJECT_MAX_AGE_DAYS", 0);
+        let quota_mb = number("PROJECTS_DISK_QUOTA_MB", 0);
+
+        Self {
+            root: PathBuf::from(PROJECTS_DIR),
+            interval: Duration::from_secs(number("REAPER_INTERVAL_SECS", DEFAULT_INTERVAL_SECS).max(1)),
+            target_max_age: Duration::from_secs(number("TARGET_MAX_AGE_HOURS", DEFAULT_TARGET_MAX_AGE_HOURS) * 3600),
+            project_max_age: (project_max_age_days > 0).then(|| Duration::from_secs(project_max_age_days * 86_400)),
+            quota_bytes: (quota_mb > 0).then(|| quota_mb * 1024 * 1024),
+            stats: Arc::new(Mutex::new(ReaperStats::default())),
+        }
+    }
+
+    pub fn stats(&self) -> ReaperStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Run forever, sweeping every `interval`
+    pub async fn run(self, locks: ProjectLocks) {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            self.sweep(&locks).await;
+        }
+    }
+
+    /// One pass: evict by age, then by quota, then record usage
+    pub async fn sweep(&self, locks: &ProjectLocks) {
+        let mut projects = self.scan(locks).await;
+        let now = SystemTime::now();
+        let idle_for = |project: &ProjectUsage| now.duration_since(project.last_access).unwrap_or_default();
+
+        let mut deleted = 0;
+        let mut evicted = 0;
+        let mut freed = 0;
+
+        // Oldest first, so quota eviction below takes the least recently used
+        projects.sort_by_key(|project| project.last_access);
+        for project in projects.iter_mut() {
+            if self.project_max_age.is_some_and(|max_age| idle_for(project) > max_age) {
+                if self.remove(locks, project, true).await {
+                    deleted += 1;
+                    freed += project.total_bytes;
+                    project.total_bytes = 0;
+                    project.target_bytes = 0;
+                }
+            } else if project.target_bytes > 0
+                && idle_for(project) > self.target_max_age
+                && self.remove(locks, project, false).await
+            {
+                evicted += 1;
+                freed += project.target_bytes;
+                project.total_bytes -= project.target_bytes;
+                project.target_bytes = 0;
+            }
+        }
+
+        if let Some(quota) = self.quota_bytes {
+            let mut total: u64 = projects.iter().map(|project| project.total_bytes).sum();
+            for project in projects.iter_mut() {
+                if total <= quota {
+                    break;
+                }
+                if project.target_bytes > 0 && self.remove(locks, project, false).await {
+                    evicted += 1;
+                    freed += project.target_bytes;
+                    total -= project.target_bytes;
+                    project.total_bytes -= project.target_bytes;
+                    project.target_bytes = 0;
+                }
+            }
+        }
+
+        let mut users: BTreeMap<String, UserDiskUsage> = BTreeMap::new();
+        for project in projects.iter().filter(|project| project.total_bytes > 0) {
+            let usage = users.entry(project.user_id.clone()).or_insert_with(|| UserDiskUsage {
+                user_id: project.user_id.clone(),
+                projects: 0,
+                bytes: 0,
+            });
+            usage.projects += 1;
+            usage.bytes += project.total_bytes;
+        }
+
+        if evicted + deleted > 0 {
+            info!(
+                "Reaper evicted {} build directories and deleted {} projects, freeing {} bytes",
+                evicted, deleted, freed
+            );
+        }
+
+        let mut stats = self.stats.lock().unwrap();
+        stats.runs += 1;
+        stats.last_run = Some(Utc::now());
+        stats.targets_evicted += evicted;
+        stats.projects_deleted += deleted;
+        stats.bytes_freed += freed;
+        stats.total_bytes = users.values().map(|usage| usage.bytes).sum();
+        stats.quota_bytes = self.quota_bytes;
+        stats.users = users.into_values().collect();
+    }
+
+    async fn scan(&self, locks: &ProjectLocks) -> Vec<ProjectUsage> {
+        let root = self.root.clone();
+        let projects = tokio::task::spawn_blocking(move || scan_projects(&root))
+            .await
+            .unwrap_or_default();
+
+        projects
+            .into_iter()
+            .map(|mut project| {
+                if let Some(accessed) = locks.last_access(&project.user_id, &project.project_id) {
+                    project.last_access = project.last_access.max(accessed);
+                }
+                project
+            })
+            .collect()
+    }
+
+    /// Remove a project's `target` (or the whole project) unless it was used
+    /// while waiting for its lock
+    async fn remove(&self, locks: &ProjectLocks, project: &ProjectUsage, whole_project: bool) -> bool {
+        let claim = locks.claim_maintenance(&project.user_id, &project.project_id);
+        let Ok(_guard) = claim.lock().await else {
+            return false;
+        };
+        if locks
+            .last_access(&project.user_id, &project.project_id)
+            .is_some_and(|accessed| accessed > project.last_access)
+        {
+            return false;
+        }
+
+        let path = if whole_project {
+            project.path.clone()
+        } else {
+            project.path.join("target")
+        };
+        let removed = tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || fs::remove_dir_all(path)
+        })
+        .await;
+
+        match removed {
+            Ok(Ok(())) => {
+                info!("Reaper removed {:?}", path);
+                if whole_project {
+                    locks.forget(&project.user_id, &project.project_id);
+                }
+                true
+            }
+            Ok(Err(e)) => {
+                warn!("Reaper failed to remove {:?}: {}", path, e);
+                false
+            }
+            Err(e) => {
+                warn!("Reaper task failed for {:?}: {}", path, e);
+                false
+            }
+        }
+    }
+}
+
+fn scan_projects(root: &Path) -> Vec<ProjectUsage> {
+    let mut projects = Vec::new();
+    let Ok(users) = fs::read_dir(root) else {
+        return projects;
+    };
+
+    for user in users.filter_map(|entry| entry.ok()).filter(|entry| entry.path().is_dir()) {
+        let Ok(entries) = fs::read_dir(user.path()) else {
+            continue;
+        };
+        for project in entries.filter_map(|entry| entry.ok()).filter(|entry| entry.path().is_dir()) {
+            let path = project.path();
+            let target_bytes = dir_size(&path.join("target"));
+            projects.push(ProjectUsage {
+                user_id: user.file_name().to_string_lossy().to_string(),
+                project_id: project.file_name().to_string_lossy().to_string(),
+                last_access: last_activity(&path),
+                target_bytes,
+                total_bytes: dir_size(&path),
+                path,
+            });
+        }
+    }
+    projects
+}
+
+/// Latest edit or build of a project, which survives server restarts
+fn last_activity(project_path: &Path) -> SystemTime {
+    let target = fs::metadata(project_path.join("target"))
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    [newest_source(project_path, Path::new("")), target]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(root: &Path, user: &str, project: &str, target_bytes: usize, age: Duration) {
+        let path = root.join(user).join(project);
+        fs::create_dir_all(path.join("src")).unwrap();
+        fs::create_dir_all(path.join("target")).unwrap();
+        fs::write(path.join("src/lib.rs"), "pub fn a() {}").unwrap();
+        fs::write(path.join("target/out"), vec![0u8; target_bytes]).unwrap();
+
+        let time = SystemTime::now() - age;
+        for file in ["src/lib.rs", "target/out"] {
+            fs::File::options()
+                .write(true)
+                .open(path.join(file))
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        }
+        fs::File::open(path.join("target")).unwrap().set_modified(time).unwrap();
+    }
+
+    fn reaper(root: &Path, quota_bytes: Option<u64>) -> Reaper {
+        Reaper {
+            root: root.to_path_buf(),
+            interval: Duration::from_secs(60),
+            target_max_age: Duration::from_secs(3600),
+            project_max_age: Some(Duration::from_secs(30 * 86_400)),
+            quota_bytes,
+            stats: Arc::new(Mutex::new(ReaperStats::default())),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_sweep_evicts_by_age_and_quota() {
+        let dir = tempfile::tempdir().unwrap();
+        let hour = Duration::from_secs(3600);
+        project(dir.path(), "alice", "idle", 100, 2 * hour);
+        project(dir.path(), "alice", "abandoned", 100, 60 * 24 * hour);
+        project(dir.path(), "bob", "older", 1000, hour / 2);
+        project(dir.path(), "bob", "fresh", 1000, Duration::ZERO);
+
+        let locks = ProjectLocks::new();
+        // Used recently through the API even though its files are old
+        locks.claim_deploy("bob", "older").lock().await.unwrap();
+
+        let reaper = reaper(dir.path(), Some(1500));
+        reaper.sweep(&locks).await;
+
+        assert!(!dir.path().join("alice/idle/target").exists());
+        assert!(dir.path().join("alice/idle/src/lib.rs").exists());
+        assert!(!dir.path().join("alice/abandoned").exists());
+        // Over quota: the least recently used build directory goes first
+        assert!(!dir.path().join("bob/fresh/target").exists());
+        assert!(dir.path().join("bob/older/target").exists());
+
+        let stats = reaper.stats();
+        assert_eq!((stats.targets_evicted, stats.projects_deleted), (2, 1));
+        assert_eq!(
+            stats.users.iter().map(|usage| usage.user_id.as_str()).collect::<Vec<_>>(),
+            vec!["alice", "bob"]
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use tokio::process::Command;
+
+use crate::errors::ServiceError;
+
+const DEFAULT_CPU_LIMIT_SECS: u64 = 600;
+const DEFAULT_MEMORY_LIMIT_MB: u64 = 4096;
+const DEFAULT_FILE_SIZE_LIMIT_MB: u64 = 512;
+const DEFAULT_DISK_LIMIT_MB: u64 = 4096;
+
+/// Extra CPU seconds between SIGXCPU and the hard-limit SIGKILL
+const CPU_GRACE_SECS: u64 = 5;
+
+/// Server environment build tooling may see. Everything else, including
+/// secrets such as `NEAR_PRIVATE_KEY`, is withheld from build scripts.
+const PASSED_ENV: [&str; 4] = ["PATH", "HOME", "CARGO_HOME", "RUSTUP_HOME"];
+
+/// How user-controlled build code (build scripts, proc macros) is confined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandboxMode {
+    /// Resource limits only, for local development
+    None,
+    /// bubblewrap: read-only root with server state hidden, writable
+    /// project, no network
+    Bubblewrap,
+}
+
+/// Per-process resource limits; `None` disables a limit
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceLimits {
+    pub cpu_secs: Option<u64>,
+    pub memory_bytes: Option<u64>,
+    pub file_size_bytes: Option<u64>,
+    /// Total size of the project directory after a build
+    pub disk_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    mode: SandboxMode,
+    limits: ResourceLimits,
+    /// Unprivileged uid/gid to drop to; requires the server to run as root
+    user: Option<(u32, u32)>,
+}
+
+impl Sandbox {
+    /// Read `BUILD_SANDBOX` (`bwrap`, the default, or `none`),
+    /// `BUILD_SANDBOX_UID`/`_GID` and the `BUILD_*_LIMIT_*` variables; a
+    /// limit of 0 disables it
+    pub fn from_env() -> Self {
+        let mode = match env::var("BUILD_SANDBOX").as_deref() {
+            Ok("bwrap") | Err(_) => SandboxMode::Bubblewrap,
+            Ok("none") => {
+                warn!("BUILD_SANDBOX=none: user build scripts run unconfined, for local development only");
+                SandboxMode::None
+            }
+            Ok(other) => {
+                warn!("Unknown BUILD_SANDBOX mode '{}', using bwrap", other);
+                SandboxMode::Bubblewrap
+            }
+        };
+
+        let limit = |name: &str, default: u64, scale: u64| {
+            let value = env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .unwrap_or(default);
+            (value > 0).then(|| value * scale)
+        };
+        let limits = ResourceLimits {
+            cpu_secs: limit("BUILD_CPU_LIMIT_SECS", DEFAULT_CPU_LIMIT_SECS, 1),
+            memory_bytes: limit("BUILD_MEMORY_LIMIT_MB", DEFAULT_MEMORY_LIMIT_MB, 1024 * 1024),
+            file_size_bytes: limit("BUILD_FILE_SIZE_LIMIT_MB", DEFAULT_FILE_SIZE_LIMIT_MB, 1024 * 1024),
+            disk_bytes: limit("BUILD_DISK_LIMIT_MB", DEFAULT_DISK_LIMIT_MB, 1024 * 1024),
+        };
+
+        let id = |name: &str| env::var(name).ok().and_then(|value| value.parse::<u32>().ok());
+        let user = id("BUILD_SANDBOX_UID").map(|uid| (uid, id("BUILD_SANDBOX_GID").unwrap_or(uid)));
+
+        Self { mode, limits, user }
+    }
+
+    /// Whether dependencies must be fetched before the build loses network access
+    pub fn is_offline(&self) -> bool {
+        self.mode == SandboxMode::Bubblewrap
+    }
+
+    /// Fail unless builds can actually be confined, so a missing or broken
+    /// bubblewrap stops the server instead of silently running builds bare
+    pub async fn ensure_available(&self) -> Result<()> {
+        if self.mode != SandboxMode::Bubblewrap {
+            return Ok(());
+        }
+
+        let mut command = Command::new("bwrap");
+        restrict_env(&mut command);
+        command.args(["--ro-bind", "/", "/", "--unshare-net", "--die-with-parent", "true"]);
+        let output = command
+            .output()
+            .await
+            .context("bubblewrap (bwrap) is required for BUILD_SANDBOX=bwrap")?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "bubblewrap cannot create a sandbox: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// `cargo <args>` run in `project_path` under the configured confinement
+    pub fn cargo_command(&self, project_path: &Path, args: &[&str]) -> Result<Command> {
+        self.command("cargo", project_path, args)
+    }
+
+    /// `program <args>` run in `project_path` under the configured confinement
+    pub fn command(&self, program: &str, project_path: &Path, args: &[&str]) -> Result<Command> {
+        let mut command = match self.mode {
+            SandboxMode::None => {
+                let mut command = Command::new(program);
+                command.args(args);
+                restrict_env(&mut command);
+                command
+            }
+            SandboxMode::Bubblewrap => {
+                let project = fs::canonicalize(project_path)
+                    .context("Failed to resolve project directory for sandbox")?;
+                let mut command = Command::new("bwrap");
+                restrict_env(&mut command);
+                command
+                    .args(["--ro-bind", "/", "/"])
+                    .args(["--dev", "/dev"])
+                    .args(["--proc", "/proc"])
+                    .args(["--tmpfs", "/tmp"]);
+                // Other users' projects, the shared caches and `.env` live
+                // under the server's directories
+                let hidden = hidden_dirs()?;
+                for dir in &hidden {
+                    command.arg("--tmpfs").arg(dir);
+                }
+                // dotenv also finds `.env` files above the working directory
+                for env_file in hidden[0].ancestors().skip(1).map(|dir| dir.join(".env")) {
+                    if env_file.is_file() {
+                        command.arg("--ro-bind").arg("/dev/null").arg(env_file);
+                    }
+                }
+                // Crates were unpacked by the fetch before the build, so the
+                // toolchain and registry stay read-only
+                for home in [rustup_home(), cargo_home()].into_iter().flatten() {
+                    if let Ok(home) = fs::canonicalize(home) {
+                        command.arg("--ro-bind").arg(&home).arg(&home);
+                        let credentials = home.join("credentials.toml");
+                        if credentials.exists() {
+                            command.arg("--ro-bind").arg("/dev/null").arg(credentials);
+                        }
+                    }
+                }
+                command
+                    .arg("--bind")
+                    .arg(&project)
+                    .arg(&project)
+                    .args(["--unshare-net", "--unshare-pid", "--die-with-parent"])
+                    .arg("--chdir")
+                    .arg(&project)
+                    .arg(program)
+                    .args(args)
+                    .env("CARGO_NET_OFFLINE", "true");
+                command
+            }
+        };
+
+        command.current_dir(project_path);
+
+        #[cfg(unix)]
+        {
+            if let Some((uid, gid)) = self.user {
+                command.uid(uid).gid(gid);
+            }
+            apply_resource_limits(&mut command, self.limits);
+        }
+
+        Ok(command)
+    }
+
+    /// `program <args>` in `project_path` with the resource limits and a
+    /// cleared environment but no confinement, for trusted host tools such
+    /// as the Docker client, which needs the daemon socket
+    pub fn host_command(&self, program: &str, project_path: &Path, args: &[&str]) -> Command {
+        let mut command = Command::new(program);
+        restrict_env(&mut command);
+        command.args(args).current_dir(project_path);
+        #[cfg(unix)]
+        apply_resource_limits(&mut command, self.limits);
+        command
+    }
+
+    /// Turn a build that died on a resource limit into a distinct error
+    pub fn check_exit(&self, status: &ExitStatus, stderr: &str) -> Result<(), ServiceError> {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(status);
+        #[cfg(not(unix))]
+        let signal = {
+            let _ = status;
+            None
+        };
+
+        match classify_violation(signal, stderr) {
+            Some(limit) => Err(ServiceError::BuildLimitExceeded {
+                limit: limit.to_string(),
+                details: self.describe(limit),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Fail when the project directory grew past the disk quota
+    pub fn check_disk_usage(&self, project_path: &Path) -> Result<(), ServiceError> {
+        let Some(limit) = self.limits.disk_bytes else {
+            return Ok(());
+        };
+
+        let used = dir_size(project_path);
+        debug!("Project {:?} uses {} bytes on disk", project_path, used);
+        if used > limit {
+            return Err(ServiceError::BuildLimitExceeded {
+                limit: "disk".to_string(),
+                details: format!("project uses {} bytes, limit is {} bytes", used, limit),
+            });
+        }
+        Ok(())
+    }
+
+    fn describe(&self, limit: &str) -> String {
+        let value = match limit {
+            "cpu_time" => self.limits.cpu_secs.map(|secs| format!("{} CPU seconds", secs)),
+            "memory" => self.limits.memory_bytes.map(|bytes| format!("{} bytes of memory", bytes)),
+            "file_size" => self.limits.file_size_bytes.map(|bytes| format!("{} bytes per file", bytes)),
+            _ => None,
+        };
+        match value {
+            Some(value) => format!("build exceeded the limit of {}", value),
+            None => "build exceeded a resource limit".to_string(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn apply_resource_limits(command: &mut Command, limits: ResourceLimits) {
+    fn set(resource: libc::c_int, soft: u64, hard: u64) -> std::io::Result<()> {
+        let limit = libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t,
+        };
+        // SAFETY: setrlimit only reads the struct we pass
+        if unsafe { libc::setrlimit(resource as _, &limit) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    // SAFETY: the closure only calls setrlimit, which is async-signal-safe
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(secs) = limits.cpu_secs {
+                set(libc::RLIMIT_CPU as _, secs, secs + CPU_GRACE_SECS)?;
+            }
+            if let Some(bytes) = limits.memory_bytes {
+                set(libc::RLIMIT_AS as _, bytes, bytes)?;
+            }
+            if let Some(bytes) = limits.file_size_bytes {
+                set(libc::RLIMIT_FSIZE as _, bytes, bytes)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Which limit a failed build hit, judged from the signal that killed cargo
+/// or from cargo's report about a rustc / build-script child
+fn classify_violation(signal: Option<i32>, stderr: &str) -> Option<&'static str> {
+    #[cfg(unix)]
+    match signal {
+        Some(libc::SIGXCPU) => return Some("cpu_time"),
+        Some(libc::SIGXFSZ) => return Some("file_size"),
+        _ => {}
+    }
+    #[cfg(not(unix))]
+    let _ = signal;
+
+    if stderr.contains("SIGXCPU") {
+        Some("cpu_time")
+    } else if stderr.contains("SIGXFSZ") || stderr.contains("File too large") {
+        Some("file_size")
+    } else if stderr.contains("memory allocation of") || stderr.contains("Cannot allocate memory") {
+        Some("memory")
+    } else {
+        None
+    }
+}
+
+/// Server state a sandboxed build must not see: the working directory,
+/// which holds `projects/`, `.env` and the default cache locations, and any
+/// cache directories configured elsewhere
+fn hidden_dirs() -> Result<Vec<PathBuf>> {
+    let working_dir = env::current_dir().context("Failed to resolve server working directory")?;
+    let mut hidden = vec![fs::canonicalize(working_dir)?];
+    if hidden[0] == Path::new("/") {
+        return Err(anyhow::anyhow!("The server must not run from / when builds are sandboxed"));
+    }
+
+    for name in ["DEPENDENCY_CACHE_DIR", "COMPILE_CACHE_DIR"] {
+        let configured = env::var_os(name).and_then(|dir| fs::canonicalize(dir).ok());
+        if let Some(dir) = configured.filter(|dir| !hidden.iter().any(|parent| dir.starts_with(parent))) {
+            hidden.push(dir);
+        }
+    }
+    Ok(hidden)
+}
+
+/// `RUSTUP_HOME`, defaulting to `~/.rustup`
+fn rustup_home() -> Option<PathBuf> {
+    env::var_os("RUSTUP_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".rustup")))
+}
+
+/// `CARGO_HOME`, defaulting to `~/.cargo`
+pub fn cargo_home() -> Option<PathBuf> {
+    env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")))
+}
+
+/// Clear a command's environment down to `PASSED_ENV`; callers add what
+/// the build itself needs, such as a build mode's `RUSTFLAGS`
+pub fn restrict_env(command: &mut Command) {
+    command.env_clear();
+    for name in PASSED_ENV {
+        if let Some(value) = env::var_os(name) {
+            command.env(name, value);
+        }
+    }
+}
+
+/// Apparent size of a directory tree, not following symlinks
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = fs::symlink_metadata(entry.path()).ok()?;
+            Some(if metadata.is_dir() {
+                dir_size(&entry.path())
+            } else {
+                metadata.len()
+            })
+        })
+        .sum()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// No confinement or limits, for tests that run real cargo commands
+    pub(crate) fn unconfined() -> Sandbox {
+        Sandbox {
+            mode: SandboxMode::None,
+            limits: ResourceLimits {
+                cpu_secs: None,
+                memory_bytes: None,
+                file_size_bytes: None,
+                disk_bytes: None,
+            },
+            user: None,
+        }
+    }
+
+    #[test]
+    fn test_classify_violation() {
+        assert_eq!(classify_violation(Some(libc::SIGXCPU), ""), Some("cpu_time"));
+        assert_eq!(
+            classify_violation(
+                None,
+                "error: could not compile `contract` (lib)\n\nCaused by:\n  process didn't exit successfully: `rustc ...` (signal: 24, SIGXCPU: CPU time limit exceeded)"
+            ),
+            Some("cpu_time")
+        );
+        assert_eq!(
+            classify_violation(None, "memory allocation of 1073741824 bytes failed"),
+            Some("memory")
+        );
+        assert_eq!(classify_violation(None, "error[E0425]: cannot find value"), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_build_commands_do_not_inherit_the_server_environment() {
+        // Set by cargo for test binaries, standing in for server secrets
+        assert!(env::var_os("CARGO_MANIFEST_DIR").is_some());
+
+        let mut command = Command::new("env");
+        restrict_env(&mut command);
+        let output = command.output().await.unwrap();
+        let listed = String::from_utf8_lossy(&output.stdout);
+        assert!(listed.lines().any(|line| line.starts_with("PATH=")));
+        assert!(!listed.contains("CARGO_MANIFEST_DIR="));
+    }
+
+    #[test]
+    fn test_sandbox_hides_server_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let sandbox = Sandbox {
+            mode: SandboxMode::Bubblewrap,
+            limits: ResourceLimits {
+                cpu_secs: None,
+                memory_bytes: None,
+                file_size_bytes: None,
+                disk_bytes: None,
+            },
+            user: None,
+        };
+        let command = sandbox.cargo_command(dir.path(), &["build"]).unwrap();
+        let args: Vec<_> = command
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let mounted = |flag: &str, path: &Path| {
+            args.windows(2)
+                .any(|pair| pair[0] == flag && Path::new(&pair[1]) == path)
+        };
+
+        let working_dir = fs::canonicalize(env::current_dir().unwrap()).unwrap();
+        assert!(mounted("--tmpfs", &working_dir));
+        assert!(mounted("--bind", &fs::canonicalize(dir.path()).unwrap()));
+        // The project is the only writable bind
+        assert_eq!(args.iter().filter(|arg| *arg == "--bind").count(), 1);
+    }
+
+    #[test]
+    fn test_disk_quota() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join("target").join("big.rlib"), vec![0u8; 2048]).unwrap();
+
+        let mut sandbox = Sandbox {
+            mode: SandboxMode::None,
+            limits: ResourceLimits {
+                cpu_secs: None,
+                memory_bytes: None,
+                file_size_bytes: None,
+                disk_bytes: Some(4096),
+            },
+            user: None,
+        };
+        assert_eq!(dir_size(dir.path()), 2048);
+        assert!(sandbox.check_disk_usage(dir.path()).is_ok());
+
+        sandbox.limits.disk_bytes = Some(1024);
+        assert!(matches!(
+            sandbox.check_disk_usage(dir.path()),
+            Err(ServiceError::BuildLimitExceeded { .. })
+        ));
+    }
+}
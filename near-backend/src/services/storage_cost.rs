@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Context, Result};
+use std::env;
+
+use crate::models::StorageEstimate;
+
+/// yoctoNEAR per byte of state, NEAR's protocol default (1 NEAR per 100 kB)
+const DEFAULT_STORAGE_PRICE_PER_BYTE: u128 = 10_000_000_000_000_000_000;
+
+/// Extra funding on top of storage: gas for the deploy and proof transactions
+/// and room for the contract's initial state (0.05 NEAR)
+const DEFAULT_FUNDING_MARGIN: u128 = 50_000_000_000_000_000_000_000;
+
+/// Sent from the new contract account back to the deployer as proof of deployment (0.03 NEAR)
+pub const PROOF_TRANSFER_AMOUNT: u128 = 30_000_000_000_000_000_000_000;
+
+/// Bytes an account record and one full-access key occupy in state
+const ACCOUNT_OVERHEAD_BYTES: u64 = 100 + 82;
+
+/// Per-record overhead NEAR charges on top of the contract code itself
+const CODE_RECORD_OVERHEAD_BYTES: u64 = 40;
+
+pub const YOCTO_PER_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+
+/// Most a deployment transfers to a new account (50 NEAR), above what the
+/// largest contract NEAR accepts needs at the default price
+const MAX_DEPLOY_FUNDING: u128 = 50 * YOCTO_PER_NEAR;
+
+/// Storage staking prices used to estimate what a deployed contract locks up
+#[derive(Debug, Clone, Copy)]
+pub struct StoragePricing {
+    pub price_per_byte: u128,
+    pub funding_margin: u128,
+}
+
+impl StoragePricing {
+    /// Read `STORAGE_PRICE_PER_BYTE` and `DEPLOY_FUNDING_MARGIN`, both in
+    /// yoctoNEAR. A value that is set but not a number is an error, checked
+    /// at startup, rather than a silent fallback to the default.
+    pub fn from_env() -> Result<Self> {
+        let yocto = |name: &str, default: u128| match env::var(name) {
+            Ok(value) => value
+                .trim()
+                .parse::<u128>()
+                .with_context(|| format!("{} must be an amount in yoctoNEAR, got '{}'", name, value)),
+            Err(_) => Ok(default),
+        };
+
+        let pricing = Self {
+            price_per_byte: yocto("STORAGE_PRICE_PER_BYTE", DEFAULT_STORAGE_PRICE_PER_BYTE)?,
+            funding_margin: yocto("DEPLOY_FUNDING_MARGIN", DEFAULT_FUNDING_MARGIN)?,
+        };
+        // Even an empty contract must be deployable
+        pricing
+            .deploy_funding(0)
+            .context("STORAGE_PRICE_PER_BYTE and DEPLOY_FUNDING_MARGIN leave no room to deploy")?;
+        Ok(pricing)
+    }
+
+    /// Balance a new account must hold to store `code_bytes` of contract code
+    pub fn estimate(&self, code_bytes: u64) -> Result<StorageEstimate> {
+        let (total_bytes, storage_cost) = self.storage_cost(code_bytes)?;
+
+        Ok(StorageEstimate {
+            code_bytes,
+            account_overhead_bytes: total_bytes - code_bytes,
+            total_bytes,
+            price_per_byte: self.price_per_byte.to_string(),
+            storage_cost: storage_cost.to_string(),
+            storage_cost_near: format_near(storage_cost),
+            recommended_funding: self.deploy_funding(code_bytes)?.to_string(),
+        })
+    }
+
+    /// yoctoNEAR to transfer to a fresh account before deploying `code_bytes`,
+    /// including the proof transfer it sends back, refused above
+    /// `MAX_DEPLOY_FUNDING`
+    pub fn deploy_funding(&self, code_bytes: u64) -> Result<u128> {
+        let (_, storage_cost) = self.storage_cost(code_bytes)?;
+        storage_cost
+            .checked_add(self.funding_margin)
+            .and_then(|funding| funding.checked_add(PROOF_TRANSFER_AMOUNT))
+            .filter(|funding| *funding <= MAX_DEPLOY_FUNDING)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Deploying {} bytes would need more than the {} NEAR a deployment may fund",
+                    code_bytes,
+                    format_near(MAX_DEPLOY_FUNDING)
+                )
+            })
+    }
+
+    fn storage_cost(&self, code_bytes: u64) -> Result<(u64, u128)> {
+        let total_bytes = code_bytes
+            .checked_add(CODE_RECORD_OVERHEAD_BYTES + ACCOUNT_OVERHEAD_BYTES)
+            .context("Contract size overflows the storage estimate")?;
+        let cost = self
+            .price_per_byte
+            .checked_mul(total_bytes as u128)
+            .context("Storage cost overflows at the configured STORAGE_PRICE_PER_BYTE")?;
+        Ok((total_bytes, cost))
+    }
+}
+
+/// yoctoNEAR as a decimal NEAR amount, trimmed of trailing zeros
+pub fn format_near(yocto: u128) -> String {
+    let whole = yocto / YOCTO_PER_NEAR;
+    let fraction = yocto % YOCTO_PER_NEAR;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:024}", fraction);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// A decimal NEAR amount such as `2.5` in yoctoNEAR
+pub fn parse_near(near: &str) -> Option<u128> {
+    let (whole, fraction) = near.trim().split_once('.').unwrap_or((near.trim(), ""));
+    if fraction.len() > 24 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let whole = if whole.is_empty() { 0 } else { whole.parse::<u128>().ok()? };
+    let fraction = format!("{:0<24}", fraction).parse::<u128>().ok()?;
+    whole.checked_mul(YOCTO_PER_NEAR)?.checked_add(fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_estimate() {
+        let pricing = StoragePricing {
+            price_per_byte: DEFAULT_STORAGE_PRICE_PER_BYTE,
+            funding_margin: DEFAULT_FUNDING_MARGIN,
+        };
+
+        // 100 kB of code costs 1 NEAR plus the account and record overhead
+        let estimate = pricing.estimate(100_000).unwrap();
+        assert_eq!(estimate.total_bytes, 100_222);
+        assert_eq!(estimate.storage_cost_near, "1.00222");
+        assert_eq!(
+            pricing.deploy_funding(100_000).unwrap(),
+            1_002_220_000_000_000_000_000_000 + DEFAULT_FUNDING_MARGIN + PROOF_TRANSFER_AMOUNT
+        );
+        assert_eq!(estimate.recommended_funding, "1082220000000000000000000");
+
+        // The largest contract NEAR accepts fits under the cap
+        assert!(pricing.deploy_funding(4 * 1024 * 1024).is_ok());
+        assert!(pricing.deploy_funding(u64::MAX).is_err());
+
+        let overpriced = StoragePricing {
+            price_per_byte: u128::MAX / 100,
+            funding_margin: DEFAULT_FUNDING_MARGIN,
+        };
+        assert!(overpriced.estimate(100_000).is_err());
+        assert!(overpriced.deploy_funding(0).is_err());
+    }
+
+    #[test]
+    fn test_format_near() {
+        assert_eq!(format_near(2 * YOCTO_PER_NEAR), "2");
+        assert_eq!(format_near(30_000_000_000_000_000_000_000), "0.03");
+        assert_eq!(format_near(1), "0.000000000000000000000001");
+    }
+
+    #[test]
+    fn test_parse_near() {
+        assert_eq!(parse_near("2"), Some(2 * YOCTO_PER_NEAR));
+        assert_eq!(parse_near("0.03"), Some(30_000_000_000_000_000_000_000));
+        assert_eq!(parse_near(&format_near(1_234_500_000_000_000_000_000_000)), Some(1_234_500_000_000_000_000_000_000));
+        assert_eq!(parse_near("-1"), None);
+        assert_eq!(parse_near("1.x"), None);
+    }
+}
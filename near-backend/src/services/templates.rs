@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::ServiceError;
+use crate::models::ProjectTemplate;
+
+const DEFAULT_TEMPLATES_DIR: &str = "templates";
+const METADATA_FILE: &str = "template.json";
+const FILES_DIR: &str = "files";
+
+/// Starter projects new projects can be created from. Each template is a
+/// directory holding `template.json` (name, description, dependencies) and
+/// `files/`, which is laid over the base project.
+#[derive(Debug, Clone)]
+pub struct TemplateRegistry {
+    root: PathBuf,
+}
+
+impl TemplateRegistry {
+    /// Read templates from `TEMPLATES_DIR`
+    pub fn from_env() -> Self {
+        let root = env::var("TEMPLATES_DIR").unwrap_or_else(|_| DEFAULT_TEMPLATES_DIR.to_string());
+        Self {
+            root: PathBuf::from(root),
+        }
+    }
+
+    /// Every template's metadata, without file contents, ordered by id
+    pub fn list(&self) -> Result<Vec<ProjectTemplate>> {
+        let mut templates = Vec::new();
+        for id in self.ids()? {
+            templates.push(self.metadata(&id)?);
+        }
+        Ok(templates)
+    }
+
+    /// A template with the files it writes into a new project
+    pub fn get(&self, id: &str) -> Result<ProjectTemplate> {
+        // Only names found in the registry reach the filesystem
+        if !self.ids()?.iter().any(|known| known == id) {
+            return Err(ServiceError::TemplateNotFound {
+                template: id.to_string(),
+            }
+            .into());
+        }
+
+        let mut template = self.metadata(id)?;
+        let files_dir = self.root.join(id).join(FILES_DIR);
+        read_files(&files_dir, Path::new(""), &mut template.files)
+            .with_context(|| format!("Failed to read files of template '{}'", id))?;
+        Ok(template)
+    }
+
+    fn ids(&self) -> Result<Vec<String>> {
+        let entries = fs::read_dir(&self.root)
+            .with_context(|| format!("Failed to read templates directory {:?}", self.root))?;
+        let mut ids: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(METADATA_FILE).is_file())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn metadata(&self, id: &str) -> Result<ProjectTemplate> {
+        let path = self.root.join(id).join(METADATA_FILE);
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        let mut template: ProjectTemplate =
+            serde_json::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))?;
+        template.id = id.to_string();
+        Ok(template)
+    }
+}
+
+fn read_files(root: &Path, relative: &Path, files: &mut BTreeMap<String, String>) -> Result<()> {
+    for entry in fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            read_files(root, &path, files)?;
+        } else {
+            let contents = fs::read_to_string(entry.path())
+                .with_context(|| format!("Failed to read {:?}", entry.path()))?;
+            files.insert(path.to_string_lossy().replace('\\', "/"), contents);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_lists_and_loads_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("token");
+        fs::create_dir_all(template.join("files/src")).unwrap();
+        fs::write(
+            template.join(METADATA_FILE),
+            r#"{
+                "name": "Token",
+                "description": "A token",
+                "category": "Token",
+                "difficulty": "Beginner",
+                "features": ["NEP-141"],
+                "dependencies": [{ "name": "near-contract-standards" }]
+            }"#,
+        )
+        .unwrap();
+        fs::write(template.join("files/src/lib.rs"), "// token").unwrap();
+        fs::create_dir_all(dir.path().join("not-a-template")).unwrap();
+
+        let registry = TemplateRegistry {
+            root: dir.path().to_path_buf(),
+        };
+        let templates = registry.list().unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].id, "token");
+        assert!(templates[0].files.is_empty());
+
+        let token = registry.get("token").unwrap();
+        assert_eq!(token.files.get("src/lib.rs").map(String::as_str), Some("// token"));
+        assert_eq!(token.dependencies[0].name, "near-contract-standards");
+
+        let missing = registry.get("../token").unwrap_err();
+        assert!(matches!(
+            missing.downcast_ref::<ServiceError>(),
+            Some(ServiceError::TemplateNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_bundled_templates_load() {
+        let registry = TemplateRegistry {
+            root: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_TEMPLATES_DIR),
+        };
+        let ids: Vec<_> = registry.list().unwrap().into_iter().map(|template| template.id).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "coin-flip",
+                "counter-contract",
+                "dao",
+                "fungible-token",
+                "hello-world",
+                "non-fungible-token"
+            ]
+        );
+        assert!(registry.get("dao").unwrap().files.len() > 1);
+    }
+}
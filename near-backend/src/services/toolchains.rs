@@ -0,0 +1,346 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item};
+
+use crate::errors::ServiceError;
+use crate::models::VersionChange;
+
+/// Template `cargo near new` produces, used as is when no versions are configured
+pub const TEMPLATE_BASE_PROJECT: &str = "base_project";
+
+const BASE_PROJECTS_DIR: &str = "base_projects";
+const TOOLCHAIN_FILE: &str = "rust-toolchain.toml";
+const SDK_CRATE: &str = "near-sdk";
+const DEPENDENCY_TABLES: [&str; 2] = ["dependencies", "dev-dependencies"];
+
+/// Rust toolchains and near-sdk versions installed on this server. The first
+/// entry of each list is the default for projects that never picked one; an
+/// empty list leaves that version to the template.
+#[derive(Debug, Clone, Default)]
+pub struct ToolchainCatalog {
+    pub toolchains: Vec<String>,
+    pub near_sdk_versions: Vec<String>,
+}
+
+/// The versions a project builds with; `None` keeps the template's
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionSelection {
+    pub toolchain: Option<String>,
+    pub near_sdk: Option<String>,
+    /// Versions the project had that were replaced because they are gone
+    pub changes: Vec<VersionChange>,
+}
+
+impl ToolchainCatalog {
+    /// Read the comma-separated `RUST_TOOLCHAINS` (rustup toolchain names) and `NEAR_SDK_VERSIONS`
+    pub fn from_env() -> Self {
+        let list = |name: &str| {
+            env::var(name)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+                .collect()
+        };
+
+        Self {
+            toolchains: list("RUST_TOOLCHAINS"),
+            near_sdk_versions: list("NEAR_SDK_VERSIONS"),
+        }
+    }
+
+    pub fn default_selection(&self) -> VersionSelection {
+        VersionSelection {
+            toolchain: self.toolchains.first().cloned(),
+            near_sdk: self.near_sdk_versions.first().cloned(),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Every combination a base project is kept for
+    pub fn combinations(&self) -> Vec<VersionSelection> {
+        let toolchains = options(&self.toolchains);
+        let sdks = options(&self.near_sdk_versions);
+        toolchains
+            .iter()
+            .flat_map(|toolchain| {
+                sdks.iter().map(|near_sdk| VersionSelection {
+                    toolchain: toolchain.clone(),
+                    near_sdk: near_sdk.clone(),
+                    changes: Vec::new(),
+                })
+            })
+            .collect()
+    }
+
+    /// Versions for a build: what the request asks for, else what the project
+    /// already uses, else the defaults. A project version that is no longer
+    /// installed moves to the default and is listed in `changes`.
+    pub fn select(
+        &self,
+        project_path: &Path,
+        toolchain: Option<&str>,
+        near_sdk: Option<&str>,
+    ) -> Result<VersionSelection, ServiceError> {
+        let defaults = self.default_selection();
+        let mut changes = Vec::new();
+        let toolchain = pick(
+            "toolchain",
+            &self.toolchains,
+            toolchain,
+            current_toolchain(project_path),
+            defaults.toolchain,
+            &mut changes,
+        )?;
+        let near_sdk = pick(
+            "near-sdk",
+            &self.near_sdk_versions,
+            near_sdk,
+            current_sdk_version(project_path),
+            defaults.near_sdk,
+            &mut changes,
+        )?;
+        Ok(VersionSelection {
+            toolchain,
+            near_sdk,
+            changes,
+        })
+    }
+}
+
+impl VersionSelection {
+    /// Directory of the base project kept for this combination
+    pub fn base_project_path(&self) -> PathBuf {
+        if self.toolchain.is_none() && self.near_sdk.is_none() {
+            return PathBuf::from(TEMPLATE_BASE_PROJECT);
+        }
+        let name = format!(
+            "{}_near-sdk-{}",
+            self.toolchain.as_deref().unwrap_or("default"),
+            self.near_sdk.as_deref().unwrap_or("default")
+        );
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+            .collect();
+        PathBuf::from(BASE_PROJECTS_DIR).join(name)
+    }
+
+    /// Pin the toolchain with `rust-toolchain.toml` and set every near-sdk
+    /// requirement in Cargo.toml, keeping its features. The toolchain file is
+    /// always the server's: the selected toolchain, else the template's pin.
+    pub fn apply(&self, project_path: &Path) -> Result<()> {
+        let toolchain_file = project_path.join(TOOLCHAIN_FILE);
+        let template_file = Path::new(TEMPLATE_BASE_PROJECT).join(TOOLCHAIN_FILE);
+        match &self.toolchain {
+            Some(toolchain) => {
+                let contents = format!(
+                    "[toolchain]\nchannel = \"{}\"\ntargets = [\"wasm32-unknown-unknown\"]\n",
+                    toolchain
+                );
+                fs::write(&toolchain_file, contents).context("Failed to write rust-toolchain.toml")?;
+            }
+            None if template_file.is_file() => {
+                if !same_file(&template_file, &toolchain_file) {
+                    fs::copy(&template_file, &toolchain_file).context("Failed to copy rust-toolchain.toml")?;
+                }
+            }
+            None => {
+                if toolchain_file.exists() {
+                    fs::remove_file(&toolchain_file).context("Failed to remove rust-toolchain.toml")?;
+                }
+            }
+        }
+
+        if let Some(version) = &self.near_sdk {
+            let manifest_path = project_path.join("Cargo.toml");
+            let content = fs::read_to_string(&manifest_path).context("Failed to read Cargo.toml")?;
+            let mut manifest: DocumentMut = content.parse().context("Failed to parse Cargo.toml")?;
+            for table in DEPENDENCY_TABLES {
+                let Some(entry) = manifest.get_mut(table).and_then(|table| table.get_mut(SDK_CRATE)) else {
+                    continue;
+                };
+                match entry.as_table_like_mut() {
+                    Some(entry) => {
+                        entry.insert("version", toml_edit::value(version.as_str()));
+                    }
+                    None => *entry = toml_edit::value(version.as_str()),
+                }
+            }
+            if manifest.to_string() != content {
+                fs::write(&manifest_path, manifest.to_string()).context("Failed to write Cargo.toml")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// near-sdk version cargo resolved in the project's Cargo.lock
+pub fn locked_sdk_version(project_path: &Path) -> Option<String> {
+    let lockfile = fs::read_to_string(project_path.join("Cargo.lock")).ok()?;
+    let lockfile: DocumentMut = lockfile.parse().ok()?;
+    let version = lockfile
+        .get("package")?
+        .as_array_of_tables()?
+        .iter()
+        .find(|package| package.get("name").and_then(Item::as_str) == Some(SDK_CRATE))?
+        .get("version")?
+        .as_str()
+        .map(String::from);
+    version
+}
+
+fn options(values: &[String]) -> Vec<Option<String>> {
+    if values.is_empty() {
+        return vec![None];
+    }
+    values.iter().cloned().map(Some).collect()
+}
+
+fn pick(
+    component: &str,
+    available: &[String],
+    requested: Option<&str>,
+    current: Option<String>,
+    default: Option<String>,
+    changes: &mut Vec<VersionChange>,
+) -> Result<Option<String>, ServiceError> {
+    let unsupported = |requested: String| ServiceError::UnsupportedVersion {
+        component: component.to_string(),
+        requested,
+        available: available.to_vec(),
+    };
+
+    if let Some(requested) = requested {
+        if !available.iter().any(|value| value == requested) {
+            return Err(unsupported(requested.to_string()));
+        }
+        return Ok(Some(requested.to_string()));
+    }
+    // Nothing is managed when the server offers no choice
+    if available.is_empty() {
+        return Ok(None);
+    }
+    match current {
+        Some(current) if available.contains(&current) => Ok(Some(current)),
+        // Removed by a server upgrade since the project last built
+        Some(previous) => {
+            changes.push(VersionChange {
+                component: component.to_string(),
+                previous,
+                current: default.clone(),
+            });
+            Ok(default)
+        }
+        None => Ok(default),
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+fn current_toolchain(project_path: &Path) -> Option<String> {
+    let file = fs::read_to_string(project_path.join(TOOLCHAIN_FILE)).ok()?;
+    let file: DocumentMut = file.parse().ok()?;
+    file.get("toolchain")?.get("channel")?.as_str().map(String::from)
+}
+
+fn current_sdk_version(project_path: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(project_path.join("Cargo.toml")).ok()?;
+    let manifest: DocumentMut = manifest.parse().ok()?;
+    let entry = manifest.get("dependencies")?.get(SDK_CRATE)?;
+    entry
+        .as_str()
+        .or_else(|| entry.get("version").and_then(Item::as_str))
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"[package]
+name = "contract"
+
+[dependencies]
+near-sdk = "5.5"
+
+[dev-dependencies]
+near-sdk = { version = "5.5", features = ["unit-testing"] }
+"#;
+
+    fn catalog() -> ToolchainCatalog {
+        ToolchainCatalog {
+            toolchains: vec!["1.86.0".to_string(), "1.87.0".to_string()],
+            near_sdk_versions: vec!["5.5".to_string(), "5.15".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_select_prefers_request_then_project_then_default() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), MANIFEST).unwrap();
+        let catalog = catalog();
+
+        let selection = catalog.select(dir.path(), None, None).unwrap();
+        assert_eq!(selection, catalog.default_selection());
+
+        let selection = catalog.select(dir.path(), Some("1.87.0"), Some("5.15")).unwrap();
+        selection.apply(dir.path()).unwrap();
+        let manifest = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+        assert!(manifest.contains("near-sdk = \"5.15\""));
+        assert!(manifest.contains("version = \"5.15\", features = [\"unit-testing\"]"));
+
+        // The project keeps its versions on later builds
+        assert_eq!(catalog.select(dir.path(), None, None).unwrap(), selection);
+        assert_eq!(
+            selection.base_project_path(),
+            PathBuf::from("base_projects/1.87.0_near-sdk-5.15")
+        );
+
+        assert!(matches!(
+            catalog.select(dir.path(), Some("nightly"), None),
+            Err(ServiceError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_removed_versions_fall_back_to_default() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), MANIFEST.replace("5.5", "5.1")).unwrap();
+        fs::write(dir.path().join(TOOLCHAIN_FILE), "[toolchain]\nchannel = \"1.80.0\"\n").unwrap();
+        let catalog = catalog();
+
+        let selection = catalog.select(dir.path(), None, None).unwrap();
+        assert_eq!(selection.toolchain.as_deref(), Some("1.86.0"));
+        assert_eq!(selection.near_sdk.as_deref(), Some("5.5"));
+        assert_eq!(
+            selection.changes[0],
+            VersionChange {
+                component: "toolchain".to_string(),
+                previous: "1.80.0".to_string(),
+                current: Some("1.86.0".to_string()),
+            }
+        );
+        assert_eq!(selection.changes.len(), 2);
+
+        selection.apply(dir.path()).unwrap();
+        assert!(catalog.select(dir.path(), None, None).unwrap().changes.is_empty());
+    }
+
+    #[test]
+    fn test_unconfigured_catalog_keeps_template() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), MANIFEST).unwrap();
+        let catalog = ToolchainCatalog::default();
+
+        let selection = catalog.select(dir.path(), None, None).unwrap();
+        assert_eq!(selection.base_project_path(), PathBuf::from(TEMPLATE_BASE_PROJECT));
+        assert_eq!(catalog.combinations(), vec![selection]);
+        assert!(catalog.select(dir.path(), Some("1.86.0"), None).is_err());
+    }
+}
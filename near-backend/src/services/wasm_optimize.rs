@@ -0,0 +1,109 @@
+use wasmparser::Validator;
+
+use crate::models::{OptimizationLevel, OptimizationReport};
+
+/// Largest contract code NEAR accepts in a deploy (4 MiB)
+pub const MAX_CONTRACT_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Run the passes `level` selects over `wasm`. The rewritten module is only
+/// returned when it validates and is smaller than the input; otherwise the
+/// report explains why the original was kept.
+pub fn optimize_wasm(wasm: &[u8], level: OptimizationLevel) -> (Option<Vec<u8>>, OptimizationReport) {
+    let original_size = wasm.len() as u64;
+    let unchanged = |note: Option<String>| OptimizationReport {
+        level,
+        original_size,
+        optimized_size: original_size,
+        passes: Vec::new(),
+        within_size_limit: original_size <= MAX_CONTRACT_SIZE,
+        note,
+    };
+
+    if level == OptimizationLevel::None {
+        return (None, unchanged(None));
+    }
+
+    // walrus drops DWARF sections on its own; names and producers are opt-out
+    let parsed = walrus::ModuleConfig::new()
+        .generate_name_section(false)
+        .generate_producers_section(false)
+        .parse(wasm);
+    let mut module = match parsed {
+        Ok(module) => module,
+        Err(e) => return (None, unchanged(Some(format!("could not parse WASM: {}", e)))),
+    };
+    let mut passes = vec!["strip-debug".to_string(), "strip-producers".to_string()];
+
+    let customs: Vec<_> = module.customs.iter().map(|(id, _)| id).collect();
+    for id in customs {
+        module.customs.delete(id);
+    }
+    passes.push("strip-custom".to_string());
+
+    if level == OptimizationLevel::Size {
+        walrus::passes::gc::run(&mut module);
+        passes.push("remove-unused".to_string());
+    }
+
+    let optimized = module.emit_wasm();
+    if let Err(e) = Validator::new().validate_all(&optimized) {
+        return (None, unchanged(Some(format!("optimized WASM failed validation: {}", e))));
+    }
+    let optimized_size = optimized.len() as u64;
+    if optimized_size >= original_size {
+        return (None, unchanged(Some("optimization did not reduce the size".to_string())));
+    }
+
+    let report = OptimizationReport {
+        level,
+        original_size,
+        optimized_size,
+        passes,
+        within_size_limit: optimized_size <= MAX_CONTRACT_SIZE,
+        note: None,
+    };
+    (Some(optimized), report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &str = r#"(module
+        (import "env" "value_return" (func $value_return (param i64 i64)))
+        (memory 1)
+        (func $unused (result i32) i32.const 42)
+        (func (export "get_greeting")
+            i64.const 0
+            i64.const 5
+            call $value_return)
+        (export "memory" (memory 0))
+        (@custom "producers_note" "built by a test"))"#;
+
+    #[test]
+    fn test_levels_strip_and_remove_unused() {
+        let wasm = wat::parse_str(MODULE).unwrap();
+
+        let (output, report) = optimize_wasm(&wasm, OptimizationLevel::None);
+        assert!(output.is_none());
+        assert!(report.passes.is_empty());
+
+        let (stripped, report) = optimize_wasm(&wasm, OptimizationLevel::Strip);
+        let stripped = stripped.unwrap();
+        assert_eq!(report.optimized_size, stripped.len() as u64);
+        assert!(report.optimized_size < report.original_size);
+        assert!(report.passes.contains(&"strip-custom".to_string()));
+
+        let (sized, report) = optimize_wasm(&wasm, OptimizationLevel::Size);
+        let sized = sized.unwrap();
+        assert!(sized.len() < stripped.len());
+        assert!(report.passes.contains(&"remove-unused".to_string()));
+        assert!(report.within_size_limit);
+
+        // Exports survive, the unreferenced function does not
+        let analysis = crate::services::wasm_report::analyze_wasm(&sized).unwrap();
+        assert_eq!(analysis.function_count, 1);
+        assert!(analysis.exports.iter().any(|export| export.name == "get_greeting"));
+        assert!(!analysis.sections.iter().any(|section| section.name.starts_with("custom:")));
+    }
+}
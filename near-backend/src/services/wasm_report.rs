@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use wasmparser::{ExternalKind, KnownCustom, Name, Parser, Payload, TypeRef};
+
+use crate::models::{WasmExport, WasmFunctionSize, WasmImport, WasmLimits, WasmReport, WasmSectionSize};
+
+/// Number of function bodies listed in `largest_functions`
+const LARGEST_FUNCTIONS: usize = 20;
+
+/// Describe a compiled contract: what it exports and imports, its memory and
+/// table limits, and where its bytes go
+pub fn analyze_wasm(wasm: &[u8]) -> Result<WasmReport> {
+    let mut report = WasmReport {
+        total_size: wasm.len() as u64,
+        exports: Vec::new(),
+        imports: Vec::new(),
+        memory: None,
+        table: None,
+        data_segments: 0,
+        data_size: 0,
+        sections: Vec::new(),
+        function_count: 0,
+        largest_functions: Vec::new(),
+    };
+
+    let mut imported_functions = 0u32;
+    let mut bodies = Vec::new();
+    let mut names = HashMap::new();
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload.context("Failed to parse WASM")?;
+
+        if let Some((id, range)) = payload.as_section() {
+            let name = match &payload {
+                Payload::CustomSection(section) => format!("custom:{}", section.name()),
+                _ => section_name(id).to_string(),
+            };
+            report.sections.push(WasmSectionSize {
+                name,
+                size: range.len() as u64,
+            });
+        }
+
+        match payload {
+            Payload::ImportSection(reader) => {
+                for import in reader.into_imports() {
+                    let import = import?;
+                    if matches!(import.ty, TypeRef::Func(_) | TypeRef::FuncExact(_)) {
+                        imported_functions += 1;
+                    }
+                    match import.ty {
+                        TypeRef::Memory(memory) if report.memory.is_none() => {
+                            report.memory = Some(WasmLimits {
+                                initial: memory.initial,
+                                maximum: memory.maximum,
+                            })
+                        }
+                        TypeRef::Table(table) if report.table.is_none() => {
+                            report.table = Some(WasmLimits {
+                                initial: table.initial,
+                                maximum: table.maximum,
+                            })
+                        }
+                        _ => {}
+                    }
+                    report.imports.push(WasmImport {
+                        module: import.module.to_string(),
+                        name: import.name.to_string(),
+                        kind: type_ref_kind(&import.ty).to_string(),
+                    });
+                }
+            }
+            Payload::MemorySection(reader) => {
+                if let Some(memory) = reader.into_iter().next() {
+                    let memory = memory?;
+                    report.memory.get_or_insert(WasmLimits {
+                        initial: memory.initial,
+                        maximum: memory.maximum,
+                    });
+                }
+            }
+            Payload::TableSection(reader) => {
+                if let Some(table) = reader.into_iter().next() {
+                    let table = table?.ty;
+                    report.table.get_or_insert(WasmLimits {
+                        initial: table.initial,
+                        maximum: table.maximum,
+                    });
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export?;
+                    if matches!(export.kind, ExternalKind::Func | ExternalKind::FuncExact) {
+                        names.entry(export.index).or_insert_with(|| export.name.to_string());
+                    }
+                    report.exports.push(WasmExport {
+                        name: export.name.to_string(),
+                        kind: external_kind(export.kind).to_string(),
+                    });
+                }
+            }
+            Payload::DataSection(reader) => {
+                for data in reader {
+                    let data = data?;
+                    report.data_segments += 1;
+                    report.data_size += data.data.len() as u64;
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                bodies.push(body.range().len() as u64);
+            }
+            Payload::CustomSection(section) => {
+                if let KnownCustom::Name(reader) = section.as_known() {
+                    // Debug names win over export names
+                    for subsection in reader {
+                        if let Ok(Name::Function(map)) = subsection {
+                            for naming in map.into_iter().flatten() {
+                                names.insert(naming.index, naming.name.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    report.function_count = bodies.len();
+    let mut functions: Vec<_> = bodies
+        .into_iter()
+        .enumerate()
+        .map(|(position, size)| {
+            let index = imported_functions + position as u32;
+            WasmFunctionSize {
+                index,
+                name: names.get(&index).cloned(),
+                size,
+            }
+        })
+        .collect();
+    functions.sort_by(|a, b| b.size.cmp(&a.size).then(a.index.cmp(&b.index)));
+    functions.truncate(LARGEST_FUNCTIONS);
+    report.largest_functions = functions;
+
+    Ok(report)
+}
+
+fn section_name(id: u8) -> &'static str {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "data_count",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
+
+fn external_kind(kind: ExternalKind) -> &'static str {
+    match kind {
+        ExternalKind::Func | ExternalKind::FuncExact => "function",
+        ExternalKind::Table => "table",
+        ExternalKind::Memory => "memory",
+        ExternalKind::Global => "global",
+        ExternalKind::Tag => "tag",
+    }
+}
+
+fn type_ref_kind(ty: &TypeRef) -> &'static str {
+    match ty {
+        TypeRef::Func(_) | TypeRef::FuncExact(_) => "function",
+        TypeRef::Table(_) => "table",
+        TypeRef::Memory(_) => "memory",
+        TypeRef::Global(_) => "global",
+        TypeRef::Tag(_) => "tag",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_contract_module() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "env" "input" (func $input (param i64)))
+                (import "env" "value_return" (func $value_return (param i64 i64)))
+                (memory 17 32)
+                (data (i32.const 1024) "hello")
+                (func $helper (result i32) i32.const 1)
+                (func (export "get_greeting")
+                    i64.const 0
+                    i64.const 5
+                    call $value_return)
+                (export "memory" (memory 0)))"#,
+        )
+        .unwrap();
+
+        let report = analyze_wasm(&wasm).unwrap();
+        assert_eq!(report.total_size, wasm.len() as u64);
+        assert_eq!(report.imports.len(), 2);
+        assert_eq!(report.imports[1].name, "value_return");
+        assert_eq!(
+            report.exports.iter().map(|export| export.name.as_str()).collect::<Vec<_>>(),
+            vec!["get_greeting", "memory"]
+        );
+        assert_eq!(report.memory.as_ref().map(|memory| (memory.initial, memory.maximum)), Some((17, Some(32))));
+        assert_eq!((report.data_segments, report.data_size), (1, 5));
+        assert_eq!(report.function_count, 2);
+
+        // Function indices count imports first; wat keeps `$helper` in the name section
+        let largest = &report.largest_functions[0];
+        assert_eq!((largest.index, largest.name.as_deref()), (3, Some("get_greeting")));
+        assert_eq!(report.largest_functions[1].name.as_deref(), Some("helper"));
+        assert!(report.sections.iter().any(|section| section.name == "code"));
+    }
+
+    #[test]
+    fn test_rejects_invalid_wasm() {
+        assert!(analyze_wasm(b"not wasm").is_err());
+    }
+}
@@ -1,10 +1,20 @@
+// Helpers for the upcoming ABI/metadata work are not wired up yet, so each
+// unused item allows dead code on its own rather than the whole module.
+
 use anyhow::{Context, Result};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
+
+/// Code hash of a contract as NEAR reports it: base58 of the WASM's sha256
+pub fn code_hash(wasm: &[u8]) -> String {
+    bs58::encode(Sha256::digest(wasm)).into_string()
+}
 
 /// Extract contract metadata/ABI from compilation artifacts
-pub fn extract_contract_metadata(project_path: &PathBuf) -> Result<Value> {
+#[allow(dead_code)]
+pub fn extract_contract_metadata(project_path: &Path) -> Result<Value> {
     // Look for ABI file generated by cargo-near
     let abi_file = project_path.join("target").join("near").join("contract_abi.json");
     
@@ -23,6 +33,7 @@ pub fn extract_contract_metadata(project_path: &PathBuf) -> Result<Value> {
 }
 
 /// Generate a basic ABI structure for contracts without explicit ABI
+#[allow(dead_code)]
 fn generate_basic_abi() -> Value {
     serde_json::json!({
         "schema_version": "0.3.0",
@@ -53,6 +64,7 @@ fn generate_basic_abi() -> Value {
 }
 
 /// Parse Rust code to extract function signatures (basic implementation)
+#[allow(dead_code)]
 pub fn extract_function_signatures(code: &str) -> Vec<FunctionSignature> {
     let mut signatures = Vec::new();
     
@@ -69,6 +81,7 @@ pub fn extract_function_signatures(code: &str) -> Vec<FunctionSignature> {
     signatures
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct FunctionSignature {
     pub name: String,
@@ -78,12 +91,14 @@ pub struct FunctionSignature {
     pub return_type: Option<String>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct Parameter {
     pub name: String,
     pub param_type: String,
 }
 
+#[allow(dead_code)]
 fn parse_function_line(line: &str) -> Option<FunctionSignature> {
     // Very basic function parsing - should be replaced with proper AST parsing
     let is_public = line.contains("pub fn");
@@ -109,6 +124,7 @@ fn parse_function_line(line: &str) -> Option<FunctionSignature> {
 }
 
 /// Validate NEAR account ID format
+#[allow(dead_code)]
 pub fn validate_account_id(account_id: &str) -> bool {
     // Basic validation - should match NEAR account ID rules
     if account_id.len() < 2 || account_id.len() > 64 {
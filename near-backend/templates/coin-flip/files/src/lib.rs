@@ -1,4 +1,4 @@
-export const COIN_FLIP_CODE = `// Find all our documentation at https://docs.near.org
+// Find all our documentation at https://docs.near.org
 use near_sdk::collections::UnorderedMap;
 use near_sdk::env::{self, log_str};
 use near_sdk::{near, AccountId, BorshStorageKey};
@@ -76,4 +76,4 @@ impl Contract {
 
         return points;
     }
-}`;
\ No newline at end of file
+}
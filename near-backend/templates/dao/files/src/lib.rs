@@ -0,0 +1,117 @@
+// Find all our documentation at https://docs.near.org
+use near_sdk::store::{IterableSet, Vector};
+use near_sdk::{env, near, require, AccountId, BorshStorageKey, PanicOnDefault};
+
+mod proposal;
+mod views;
+
+pub use crate::proposal::{Proposal, ProposalKind, ProposalStatus};
+
+#[near(serializers = [borsh])]
+#[derive(BorshStorageKey)]
+enum StorageKey {
+    Members,
+    Proposals,
+}
+
+// Define the contract structure
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct Dao {
+    members: IterableSet<AccountId>,
+    proposals: Vector<Proposal>,
+    /// Approvals a proposal needs before it is executed
+    threshold: u32,
+}
+
+// Implement the contract structure
+#[near]
+impl Dao {
+    /// Start the DAO with its founding council and the number of approvals a proposal needs
+    #[init]
+    pub fn new(members: Vec<AccountId>, threshold: u32) -> Self {
+        require!(!members.is_empty(), "A DAO needs at least one member");
+        require!(
+            threshold > 0 && threshold as usize <= members.len(),
+            "Threshold must be between 1 and the number of members"
+        );
+
+        let mut set = IterableSet::new(StorageKey::Members);
+        for member in members {
+            set.insert(member);
+        }
+        Self {
+            members: set,
+            proposals: Vector::new(StorageKey::Proposals),
+            threshold,
+        }
+    }
+
+    /// The caller, who must be a member
+    pub(crate) fn assert_member(&self) -> AccountId {
+        let caller = env::predecessor_account_id();
+        require!(self.members.contains(&caller), "Only DAO members can do this");
+        caller
+    }
+}
+
+/*
+ * The rest of this file holds the inline tests for the code above
+ * to run these, the command will be: `cargo test`
+ * Learn more about Rust tests: https://doc.rust-lang.org/book/ch11-01-writing-tests.html
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn account(name: &str) -> AccountId {
+        name.parse().unwrap()
+    }
+
+    fn call_as(name: &str) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account(name))
+            .build());
+    }
+
+    #[test]
+    fn text_proposal_passes_at_threshold() {
+        call_as("alice.near");
+        let mut dao = Dao::new(vec![account("alice.near"), account("bob.near")], 2);
+
+        let id = dao.add_proposal("Adopt a logo".to_string(), ProposalKind::Text);
+        dao.vote(id, true);
+        assert_eq!(dao.get_proposal(id).unwrap().status, ProposalStatus::InProgress);
+
+        call_as("bob.near");
+        dao.vote(id, true);
+        assert_eq!(dao.get_proposal(id).unwrap().status, ProposalStatus::Approved);
+    }
+
+    #[test]
+    fn add_member_by_vote() {
+        call_as("alice.near");
+        let mut dao = Dao::new(vec![account("alice.near")], 1);
+
+        let id = dao.add_proposal(
+            "Welcome carol".to_string(),
+            ProposalKind::AddMember {
+                account_id: account("carol.near"),
+            },
+        );
+        dao.vote(id, true);
+        assert!(dao.get_members().contains(&account("carol.near")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only DAO members can do this")]
+    fn outsiders_cannot_propose() {
+        call_as("alice.near");
+        let mut dao = Dao::new(vec![account("alice.near")], 1);
+
+        call_as("mallory.near");
+        dao.add_proposal("Drain the treasury".to_string(), ProposalKind::Text);
+    }
+}
@@ -0,0 +1,110 @@
+use near_sdk::{env, log, near, require, AccountId, NearToken, Promise};
+
+use crate::{Dao, DaoExt};
+
+/// What happens when a proposal is approved
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProposalKind {
+    /// A motion with no on-chain effect
+    Text,
+    /// Send NEAR from the DAO's balance
+    Transfer {
+        receiver_id: AccountId,
+        amount: NearToken,
+    },
+    AddMember {
+        account_id: AccountId,
+    },
+    RemoveMember {
+        account_id: AccountId,
+    },
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProposalStatus {
+    InProgress,
+    Approved,
+    Rejected,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug)]
+pub struct Proposal {
+    pub proposer: AccountId,
+    pub description: String,
+    pub kind: ProposalKind,
+    pub status: ProposalStatus,
+    pub approvals: Vec<AccountId>,
+    pub rejections: Vec<AccountId>,
+}
+
+#[near]
+impl Dao {
+    /// Members submit proposals; returns the new proposal's id
+    pub fn add_proposal(&mut self, description: String, kind: ProposalKind) -> u32 {
+        let proposer = self.assert_member();
+        self.proposals.push(Proposal {
+            proposer,
+            description,
+            kind,
+            status: ProposalStatus::InProgress,
+            approvals: Vec::new(),
+            rejections: Vec::new(),
+        });
+        let id = self.proposals.len() - 1;
+        log!("Proposal {} added", id);
+        id
+    }
+
+    /// Approve or reject a proposal; it runs as soon as it reaches the threshold
+    pub fn vote(&mut self, id: u32, approve: bool) {
+        let voter = self.assert_member();
+        let threshold = self.threshold as usize;
+        let members = self.members.len() as usize;
+
+        let proposal = self.proposals.get_mut(id).unwrap_or_else(|| env::panic_str("No such proposal"));
+        require!(proposal.status == ProposalStatus::InProgress, "Voting on this proposal has ended");
+        require!(
+            !proposal.approvals.contains(&voter) && !proposal.rejections.contains(&voter),
+            "Already voted"
+        );
+
+        if approve {
+            proposal.approvals.push(voter);
+        } else {
+            proposal.rejections.push(voter);
+        }
+
+        if proposal.approvals.len() >= threshold {
+            proposal.status = ProposalStatus::Approved;
+            let kind = proposal.kind.clone();
+            log!("Proposal {} approved", id);
+            self.execute(kind);
+        } else if members - proposal.rejections.len() < threshold {
+            // Not enough members left to approve it
+            proposal.status = ProposalStatus::Rejected;
+            log!("Proposal {} rejected", id);
+        }
+    }
+
+    fn execute(&mut self, kind: ProposalKind) {
+        match kind {
+            ProposalKind::Text => {}
+            ProposalKind::Transfer { receiver_id, amount } => {
+                Promise::new(receiver_id).transfer(amount);
+            }
+            ProposalKind::AddMember { account_id } => {
+                self.members.insert(account_id);
+            }
+            ProposalKind::RemoveMember { account_id } => {
+                require!(
+                    self.members.len() > self.threshold,
+                    "Removing a member would leave too few to reach the threshold"
+                );
+                self.members.remove(&account_id);
+            }
+        }
+    }
+}
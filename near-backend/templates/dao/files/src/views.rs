@@ -0,0 +1,28 @@
+use near_sdk::{near, AccountId};
+
+use crate::{Dao, DaoExt, Proposal};
+
+#[near]
+impl Dao {
+    pub fn get_members(&self) -> Vec<AccountId> {
+        self.members.iter().cloned().collect()
+    }
+
+    pub fn get_threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub fn get_proposal(&self, id: u32) -> Option<Proposal> {
+        self.proposals.get(id).cloned()
+    }
+
+    /// Proposals from `from_index`, at most `limit` of them
+    pub fn get_proposals(&self, from_index: u32, limit: u32) -> Vec<Proposal> {
+        self.proposals
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+}
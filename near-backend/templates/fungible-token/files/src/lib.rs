@@ -1,4 +1,4 @@
-export const FUNGIBLE_TOKEN_CODE = `/*!
+/*!
 Fungible Token implementation with JSON serialization.
 NOTES:
   - The maximum balance value is limited by U128 (2**128 - 1).
@@ -49,7 +49,7 @@ enum StorageKey {
 
 #[near]
 impl Contract {
-    /// Initializes the contract with the given total supply owned by the given \`owner_id\` with
+    /// Initializes the contract with the given total supply owned by the given `owner_id` with
     /// default metadata (for example purposes only).
     #[init]
     pub fn new_default_meta(owner_id: AccountId, total_supply: U128) -> Self {
@@ -68,7 +68,7 @@ impl Contract {
         )
     }
 
-    /// Initializes the contract with the given total supply owned by the given \`owner_id\` with
+    /// Initializes the contract with the given total supply owned by the given `owner_id` with
     /// the given fungible token metadata.
     #[init]
     pub fn new(owner_id: AccountId, total_supply: U128, metadata: FungibleTokenMetadata) -> Self {
@@ -823,4 +823,4 @@ mod tests {
 
         contract.ft_transfer_call(user1(), transfer_amount.into(), None, "".to_string());
     }
-}`;
\ No newline at end of file
+}
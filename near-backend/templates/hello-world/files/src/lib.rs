@@ -0,0 +1,32 @@
+// Find all our documentation at https://docs.near.org
+use near_sdk::{log, near};
+
+// Define the contract structure
+#[near(contract_state)]
+pub struct Contract {
+    greeting: String,
+}
+
+// Define the default, which automatically initializes the contract
+impl Default for Contract {
+    fn default() -> Self {
+        Self {
+            greeting: "Hello".to_string(),
+        }
+    }
+}
+
+// Implement the contract structure
+#[near]
+impl Contract {
+    // Public method - returns the greeting saved, defaulting to DEFAULT_GREETING
+    pub fn get_greeting(&self) -> String {
+        self.greeting.clone()
+    }
+
+    // Public method - accepts a greeting, such as "howdy", and records it
+    pub fn set_greeting(&mut self, greeting: String) {
+        log!("Saving greeting: {}", greeting);
+        self.greeting = greeting;
+    }
+}
@@ -1,4 +1,4 @@
-export const NFT_CODE = `/*!
+/*!
 Non-Fungible Token implementation with JSON serialization.
 NOTES:
   - The maximum balance value is limited by U128 (2**128 - 1).
@@ -53,7 +53,7 @@ enum StorageKey {
 
 #[near]
 impl Contract {
-    /// Initializes the contract owned by \`owner_id\` with
+    /// Initializes the contract owned by `owner_id` with
     /// default metadata (for example purposes only).
     #[init]
     pub fn new_default_meta(owner_id: AccountId) -> Self {
@@ -87,11 +87,11 @@ impl Contract {
         }
     }
 
-    /// Mint a new token with ID=\`token_id\` belonging to \`token_owner_id\`.
+    /// Mint a new token with ID=`token_id` belonging to `token_owner_id`.
     ///
     /// Since this example implements metadata, it also requires per-token metadata to be provided
-    /// in this call. \`self.tokens.mint\` will also require it to be Some, since
-    /// \`StorageKey::TokenMetadata\` was provided at initialization.
+    /// in this call. `self.tokens.mint` will also require it to be Some, since
+    /// `StorageKey::TokenMetadata` was provided at initialization.
     ///
     #[payable]
     pub fn nft_mint(
@@ -445,4 +445,4 @@ mod tests {
             .build());
         assert!(!contract.nft_is_approved(token_id.clone(), accounts(1), Some(1)));
     }
-}`;
\ No newline at end of file
+}
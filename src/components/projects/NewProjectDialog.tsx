@@ -23,9 +23,8 @@ import {
 import { Input } from '@/components/ui/input';
 import { Button } from '@/components/ui/button';
 import { Tabs, TabsContent, TabsList, TabsTrigger } from '@/components/ui/tabs';
-import { PROJECT_TEMPLATES } from '@/lib/templates';
+import { ProjectTemplate } from '@/lib/api';
 import { ScrollArea } from '@/components/ui/scroll-area';
-import { Badge } from '@/components/ui/badge';
 import {
   Form,
   FormControl,
@@ -35,7 +34,7 @@ import {
   FormMessage,
   FormDescription,
 } from '@/components/ui/form';
-import { useToast } from '@/hooks/use-toast';
+import { templateIcon, useTemplates } from '@/hooks/use-templates';
 import { cn } from '@/lib/utils';
 
 const formSchema = z.object({
@@ -49,7 +48,7 @@ const formSchema = z.object({
 interface NewProjectDialogProps {
   open: boolean;
   onOpenChange: (open: boolean) => void;
-  onCreateProject: (data: { name: string; description: string; template?: typeof PROJECT_TEMPLATES[0] }) => void;
+  onCreateProject: (data: { name: string; description: string; template?: ProjectTemplate }) => void;
 }
 
 export function NewProjectDialog({
@@ -58,8 +57,8 @@ export function NewProjectDialog({
   onCreateProject,
 }: NewProjectDialogProps) {
   const [activeTab, setActiveTab] = useState<'blank' | 'template'>('blank');
-  const [selectedTemplate, setSelectedTemplate] = useState<typeof PROJECT_TEMPLATES[0] | null>(null);
-  const { toast } = useToast();
+  const [selectedTemplate, setSelectedTemplate] = useState<ProjectTemplate | null>(null);
+  const { templates, isLoading: templatesLoading } = useTemplates();
 
   const form = useForm<z.infer<typeof formSchema>>({
     resolver: zodResolver(formSchema),
@@ -87,19 +86,10 @@ export function NewProjectDialog({
   }, [selectedTemplate, form]);
 
   const handleSubmit = (data: z.infer<typeof formSchema>) => {
-    // Don't allow creating project from OpenZeppelin templates
-    if (selectedTemplate?.isOpenZeppelin) {
-      toast({
-        title: "Coming Soon",
-        description: "OpenZeppelin templates will be available soon!",
-      });
-      return;
-    }
-
     onCreateProject({
       name: data.name.trim(),
       description: data.description?.trim() || '',
-      template: selectedTemplate,
+      template: selectedTemplate ?? undefined,
     });
     onOpenChange(false);
   };
@@ -236,47 +226,40 @@ export function NewProjectDialog({
               <div className="border rounded-lg overflow-hidden">
                 <ScrollArea className="h-[400px]">
                   <div className="divide-y">
-                    {PROJECT_TEMPLATES.map((template, index) => (
-                      <div
-                        key={index}
-                        onClick={() => !template.isOpenZeppelin && setSelectedTemplate(template)}
-                        className={cn(
-                          "p-4 flex items-center gap-4 transition-colors",
-                          !template.isOpenZeppelin && "cursor-pointer hover:bg-accent",
-                          selectedTemplate?.name === template.name && "bg-accent",
-                          template.isOpenZeppelin && "opacity-75"
-                        )}
-                      >
-                        <div className="flex-none p-3 rounded-lg bg-primary/10">
-                          <template.icon className="h-5 w-5 text-primary" />
-                        </div>
+                    {templatesLoading && (
+                      <div className="p-4 text-sm text-muted-foreground">Loading templates...</div>
+                    )}
+                    {templates.map((template) => {
+                      const Icon = templateIcon(template);
+                      return (
+                        <div
+                          key={template.id}
+                          onClick={() => setSelectedTemplate(template)}
+                          className={cn(
+                            "p-4 flex items-center gap-4 transition-colors cursor-pointer hover:bg-accent",
+                            selectedTemplate?.id === template.id && "bg-accent"
+                          )}
+                        >
+                          <div className="flex-none p-3 rounded-lg bg-primary/10">
+                            <Icon className="h-5 w-5 text-primary" />
+                          </div>
                         
-                        <div className="flex-1 min-w-0">
-                          <div className="flex items-center gap-2 mb-1">
-                            <h3 className="font-medium">{template.name}</h3>
-                            {template.isOpenZeppelin && (
-                              <div className="flex items-center gap-2">
-                                <Badge variant="outline" className="bg-blue-500/10 text-blue-500">
-                                  OpenZeppelin
-                                </Badge>
-                                <Badge variant="outline" className="bg-yellow-500/10 text-yellow-500">
-                                  Coming Soon
-                                </Badge>
-                              </div>
-                            )}
+                          <div className="flex-1 min-w-0">
+                            <div className="flex items-center gap-2 mb-1">
+                              <h3 className="font-medium">{template.name}</h3>
+                            </div>
+                            <p className="text-sm text-muted-foreground line-clamp-2">
+                              {template.description}
+                            </p>
                           </div>
-                          <p className="text-sm text-muted-foreground line-clamp-2">
-                            {template.description}
-                          </p>
-                        </div>
 
-                        <ArrowRight className={cn(
-                          "flex-none h-4 w-4 text-muted-foreground transition-opacity",
-                          selectedTemplate?.name === template.name ? "opacity-100" : "opacity-0",
-                          template.isOpenZeppelin && "opacity-0"
-                        )} />
-                      </div>
-                    ))}
+                          <ArrowRight className={cn(
+                            "flex-none h-4 w-4 text-muted-foreground transition-opacity",
+                            selectedTemplate?.id === template.id ? "opacity-100" : "opacity-0"
+                          )} />
+                        </div>
+                      );
+                    })}
                   </div>
                 </ScrollArea>
               </div>
@@ -331,7 +314,6 @@ export function NewProjectDialog({
                       <Button 
                         type="submit" 
                         className="gap-2"
-                        disabled={selectedTemplate.isOpenZeppelin}
                       >
                         <Sparkles className="h-4 w-4" />
                         Create from Template
@@ -1,14 +1,14 @@
 import { useState } from 'react';
-import { PROJECT_TEMPLATES } from '@/lib/templates';
+import { ProjectTemplate } from '@/lib/api';
+import { useTemplates } from '@/hooks/use-templates';
 import { TemplateListRow } from './TemplateListRow';
 import { LoadingSkeleton } from '@/components/LoadingSkeleton';
 import { Code2, Sparkles } from 'lucide-react';
 import { SortOption } from './ProjectTabs';
-import { useToast } from '@/hooks/use-toast';
 
 interface TemplateListProps {
   searchQuery: string;
-  onUseTemplate: (template: typeof PROJECT_TEMPLATES[0]) => void;
+  onUseTemplate: (data: { name: string; description: string; template?: ProjectTemplate }) => Promise<void>;
   isLoading?: boolean;
   sortBy?: SortOption['value'];
 }
@@ -20,33 +20,14 @@ export function TemplateList({
   sortBy = 'name_asc'
 }: TemplateListProps) {
   const [creatingTemplate, setCreatingTemplate] = useState<string | null>(null);
-  const { toast } = useToast();
+  const { templates, isLoading: templatesLoading, error } = useTemplates();
 
-  const handleUseTemplate = async (template: typeof PROJECT_TEMPLATES[0]) => {
+  const handleUseTemplate = async (template: ProjectTemplate) => {
     // Prevent multiple clicks on same template
-    if (creatingTemplate === template.name) return;
-
-    // Don't allow using OpenZeppelin templates yet
-    if (template.isOpenZeppelin) {
-      toast({
-        title: "Coming Soon",
-        description: "OpenZeppelin templates will be available soon!",
-      });
-      return;
-    }
-
-    // Ensure template code exists
-    if (!template.code) {
-      toast({
-        title: "Error",
-        description: "Template code is missing",
-        variant: "destructive",
-      });
-      return;
-    }
+    if (creatingTemplate === template.id) return;
 
     // Set loading state for this specific template
-    setCreatingTemplate(template.name);
+    setCreatingTemplate(template.id);
 
     try {
       await onUseTemplate({
@@ -60,11 +41,11 @@ export function TemplateList({
     }
   };
 
-  if (isLoading) {
+  if (isLoading || templatesLoading) {
     return <LoadingSkeleton />;
   }
 
-  let filteredTemplates = PROJECT_TEMPLATES.filter(template =>
+  let filteredTemplates = templates.filter(template =>
     template.name.toLowerCase().includes(searchQuery.toLowerCase()) ||
     template.description.toLowerCase().includes(searchQuery.toLowerCase())
   );
@@ -96,11 +77,13 @@ export function TemplateList({
           </div>
           
           <h3 className="text-2xl font-semibold mt-6">
-            No Templates Found
+            {error ? "Templates Unavailable" : "No Templates Found"}
           </h3>
           
           <p className="text-muted-foreground mt-2">
-            Try adjusting your search terms or clear the filter to see all available templates
+            {error
+              ? "The template list could not be loaded. Please try again later"
+              : "Try adjusting your search terms or clear the filter to see all available templates"}
           </p>
         </div>
       </div>
@@ -128,12 +111,12 @@ export function TemplateList({
             </tr>
           </thead>
           <tbody className="divide-y">
-            {filteredTemplates.map((template, index) => (
+            {filteredTemplates.map((template) => (
               <TemplateListRow
-                key={index}
+                key={template.id}
                 template={template}
                 onUseTemplate={handleUseTemplate}
-                isCreating={creatingTemplate === template.name}
+                isCreating={creatingTemplate === template.id}
               />
             ))}
           </tbody>
@@ -4,11 +4,12 @@ import { Tooltip, TooltipContent, TooltipTrigger, TooltipProvider } from '@/comp
 import { Loader2 } from 'lucide-react';
 import { cn } from '@/lib/utils';
 import { useState, useRef, useEffect } from 'react';
-import { PROJECT_TEMPLATES } from '@/lib/templates';
+import { ProjectTemplate } from '@/lib/api';
+import { templateIcon } from '@/hooks/use-templates';
 
 interface TemplateListRowProps {
-  template: typeof PROJECT_TEMPLATES[0];
-  onUseTemplate: (template: typeof PROJECT_TEMPLATES[0]) => void;
+  template: ProjectTemplate;
+  onUseTemplate: (template: ProjectTemplate) => void;
   isCreating?: boolean;
 }
 
@@ -61,7 +62,7 @@ export function TemplateListRow({ template, onUseTemplate, isCreating = false }:
     }
   };
 
-  const Icon = template.icon;
+  const Icon = templateIcon(template);
 
   return (
     <tr className="group hover:bg-muted/30 border-b border-border transition-colors">
@@ -92,14 +93,6 @@ export function TemplateListRow({ template, onUseTemplate, isCreating = false }:
                   {template.difficulty}
                 </Badge>
               )}
-              {template.isOpenZeppelin && (
-                <Badge
-                  variant="outline"
-                  className="text-xs px-2 py-0.5 border text-orange-800 bg-orange-50 border-orange-200 dark:text-orange-200 dark:bg-orange-950/60 dark:border-orange-700 font-medium"
-                >
-                  ⚡ OpenZeppelin
-                </Badge>
-              )}
             </div>
           </div>
         </div>
@@ -137,15 +130,13 @@ export function TemplateListRow({ template, onUseTemplate, isCreating = false }:
             size="sm"
             className="bg-teal-600 text-white hover:bg-teal-700"
             onClick={() => onUseTemplate(template)}
-            disabled={isCreating || template.isOpenZeppelin}
+            disabled={isCreating}
           >
             {isCreating ? (
               <>
                 <Loader2 className="h-4 w-4 animate-spin mr-2" />
                 Creating...
               </>
-            ) : template.isOpenZeppelin ? (
-              "Coming Soon"
             ) : (
               "Use Template"
             )}
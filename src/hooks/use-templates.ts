@@ -0,0 +1,67 @@
+import { useEffect, useState } from 'react';
+import {
+  Code2,
+  MessageCircle,
+  Coins,
+  Image,
+  Dices,
+  Landmark,
+  type LucideIcon,
+} from 'lucide-react';
+import { fetchTemplates, ProjectTemplate } from '@/lib/api';
+
+const TEMPLATE_ICONS: Record<string, LucideIcon> = {
+  'hello-world': MessageCircle,
+  'counter-contract': Code2,
+  'fungible-token': Coins,
+  'non-fungible-token': Image,
+  'coin-flip': Dices,
+  'dao': Landmark,
+};
+
+/**
+ * Icon shown for a template, falling back to a generic one for templates
+ * the frontend does not know yet
+ */
+export function templateIcon(template: ProjectTemplate): LucideIcon {
+  return TEMPLATE_ICONS[template.id] ?? Code2;
+}
+
+// Templates only change with a backend deploy, so one request per page load is enough
+let cached: Promise<ProjectTemplate[]> | null = null;
+
+/**
+ * Project templates from the backend's `GET /templates`
+ */
+export function useTemplates() {
+  const [templates, setTemplates] = useState<ProjectTemplate[]>([]);
+  const [isLoading, setIsLoading] = useState(true);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    let active = true;
+    if (!cached) {
+      cached = fetchTemplates();
+      // Let a later mount retry after a failure
+      cached.catch(() => {
+        cached = null;
+      });
+    }
+    cached
+      .then((loaded) => {
+        if (active) setTemplates(loaded);
+      })
+      .catch((e) => {
+        console.error('Error loading templates:', e);
+        if (active) setError(e instanceof Error ? e.message : 'Failed to load templates');
+      })
+      .finally(() => {
+        if (active) setIsLoading(false);
+      });
+    return () => {
+      active = false;
+    };
+  }, []);
+
+  return { templates, isLoading, error };
+}
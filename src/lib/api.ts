@@ -1,20 +1,31 @@
 import axios from 'axios';
 import { CompilationResult, DeploymentResult, MethodCallResult } from './types';
 import { API_URL } from './config';
+import { supabase } from './supabase';
 
 // Create axios instance with default config
 const api = axios.create({
   baseURL: API_URL,
   headers: {
     'Content-Type': 'application/json',
-    'Authorization': `Bearer ${import.meta.env.VITE_API_KEY}`
   },
 });
 
+// The backend identifies the user from the Supabase session's access token
+api.interceptors.request.use(async (config) => {
+  const { data } = await supabase.auth.getSession();
+  const token = data.session?.access_token;
+  if (token) {
+    config.headers.Authorization = `Bearer ${token}`;
+  }
+  return config;
+});
+
 interface CompileRequest {
   user_id: string;
   project_id: string;
   code: string;
+  template?: string;
 }
 
 interface DeployRequest {
@@ -40,6 +51,24 @@ interface ApiResponse<T> {
   } | null;
 }
 
+export interface TemplateDependency {
+  name: string;
+  version?: string | null;
+  features?: string[];
+}
+
+export interface ProjectTemplate {
+  id: string;
+  name: string;
+  description: string;
+  category: string;
+  difficulty: string;
+  features: string[];
+  dependencies: TemplateDependency[];
+  /** Only returned by `GET /templates/{id}`, keyed by path relative to the project root */
+  files?: Record<string, string>;
+}
+
 interface CompileResponse {
   success: boolean;
   exit_code: number;
@@ -59,13 +88,15 @@ interface CompileResponse {
 export async function compileContract(
   code: string,
   userId: string,
-  projectId: string
+  projectId: string,
+  template?: string
 ): Promise<CompilationResult> {
   try {
     const payload: CompileRequest = {
       user_id: userId,
       project_id: projectId,
       code,
+      template,
     };
 
     const { data: response } = await api.post<ApiResponse<CompileResponse>>('/compile', payload);
@@ -98,6 +129,54 @@ export async function compileContract(
   }
 }
 
+/**
+ * List the project templates the backend offers, without their files
+ */
+export async function fetchTemplates(): Promise<ProjectTemplate[]> {
+  try {
+    const { data: response } = await api.get<ApiResponse<ProjectTemplate[]>>('/templates');
+
+    if (!response.success || !response.data) {
+      throw new Error(response.error?.message || 'Failed to load templates');
+    }
+
+    return response.data;
+  } catch (error) {
+    if (axios.isAxiosError(error) && error.response?.data) {
+      const apiError = error.response.data as ApiResponse<any>;
+      throw new Error(apiError.error?.message || 'Failed to load templates');
+    }
+    throw error instanceof Error
+      ? error
+      : new Error('Failed to load templates');
+  }
+}
+
+/**
+ * Fetch one project template with the files it starts a project with
+ */
+export async function fetchTemplate(id: string): Promise<ProjectTemplate> {
+  try {
+    const { data: response } = await api.get<ApiResponse<ProjectTemplate>>(
+      `/templates/${encodeURIComponent(id)}`
+    );
+
+    if (!response.success || !response.data) {
+      throw new Error(response.error?.message || 'Failed to load template');
+    }
+
+    return response.data;
+  } catch (error) {
+    if (axios.isAxiosError(error) && error.response?.data) {
+      const apiError = error.response.data as ApiResponse<any>;
+      throw new Error(apiError.error?.message || 'Failed to load template');
+    }
+    throw error instanceof Error
+      ? error
+      : new Error('Failed to load template');
+  }
+}
+
 /**
  * Deploy a compiled contract to NEAR Testnet
  */
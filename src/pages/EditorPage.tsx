@@ -264,7 +264,12 @@ export function EditorPage() {
 
     setIsCompiling(true);
     try {
-      const result = await compileContract(project.code, user.id, project.id);
+      const result = await compileContract(
+        project.code,
+        user.id,
+        project.id,
+        project.metadata?.template
+      );
       setLastCompilationResult(result);
 
       // Save compilation result to history
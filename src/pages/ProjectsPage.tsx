@@ -11,7 +11,8 @@ import { useAuth } from '@/App';
 import { UserNav } from '@/components/UserNav';
 import { ProjectList } from '@/components/projects/ProjectList';
 import { TemplateList } from '@/components/projects/TemplateList';
-import { PROJECT_TEMPLATES } from '@/lib/templates';
+import { fetchTemplate, ProjectTemplate } from '@/lib/api';
+import { useTemplates } from '@/hooks/use-templates';
 import { ProjectHeader } from '@/components/projects/ProjectHeader';
 import { ProjectTabs, SortOption } from '@/components/projects/ProjectTabs';
 import { ProjectEditDialog } from '@/components/projects/ProjectEditDialog';
@@ -35,6 +36,7 @@ export function ProjectsPage() {
   const navigate = useNavigate();
   const { toast } = useToast();
   const { user } = useAuth();
+  const { templates } = useTemplates();
 
 
   useEffect(() => {
@@ -110,7 +112,7 @@ export function ProjectsPage() {
   const handleCreateProject = async (data: { 
     name: string; 
     description: string; 
-    template?: typeof PROJECT_TEMPLATES[0];
+    template?: ProjectTemplate;
   }) => {
     if (!user) {
       toast({
@@ -123,6 +125,9 @@ export function ProjectsPage() {
     
     try {
 
+      // The template's other files are added by the backend on the first compile
+      const template = data.template ? await fetchTemplate(data.template.id) : undefined;
+
       // Create project with empty code if no template is provided
       const { data: project, error } = await supabase
         .from('projects')
@@ -130,7 +135,8 @@ export function ProjectsPage() {
           user_id: user.id,
           name: data.name,
           description: data.description || '',
-          code: data.template?.code || '', // Empty string if no template
+          code: template?.files?.['src/lib.rs'] || '', // Empty string if no template
+          ...(template && { metadata: { template: template.id } }),
           updated_at: new Date().toISOString(),
           last_activity_at: new Date().toISOString(),
         })
@@ -231,7 +237,7 @@ export function ProjectsPage() {
       id: 'templates' as const,
       label: 'Templates',
       icon: Sparkles,
-      count: PROJECT_TEMPLATES.length,
+      count: templates.length,
     },
   ];
 